@@ -1,10 +1,15 @@
+use std::str::FromStr;
+
 use rand::{distributions::{Distribution, Standard}, Rng};
+use serde::{Deserialize, Serialize};
 
 const LAMBDA: f64 = 1.0507009873554805;
 const ALPHA: f64  = 1.6732632423543772;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum ActivationFunction {
+/// Serialized under the lowercase names neat-python uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivationFunction {
     Sigmoid,
     Tanh,
     Sin,
@@ -26,6 +31,13 @@ pub(crate) enum ActivationFunction {
 }
 
 impl ActivationFunction {
+    pub const ALL: [Self; 18] = [
+        Self::Sigmoid, Self::Tanh, Self::Sin, Self::Gauss, Self::Relu, 
+        Self::Elu, Self::Lelu, Self::Selu, Self::SoftPlus, Self::Identity, 
+        Self::Clamped, Self::Inv, Self::Log, Self::Exp, Self::Abs, Self::Hat,
+        Self::Square, Self::Cube,
+    ];
+
     pub fn activate(&self, val: f64) -> f64 {
         match self {
             Self::Sigmoid => {
@@ -34,7 +46,7 @@ impl ActivationFunction {
             }
             Self::Tanh => (val * 2.5).clamp(-60.0, 60.0).tanh(),
             Self::Sin => (val * 5.0).clamp(-60.0, 60.0).sin(),
-            Self::Gauss => -5.0 * val.clamp(-3.4, 3.4).powi(2),
+            Self::Gauss => (-5.0 * val.clamp(-3.4, 3.4).powi(2)).exp(),
             Self::Relu => if val > 0.0 { val } else { 0.0 } 
             Self::Elu => if val > 0.0 { val } else { val.exp_m1() }
             Self::Lelu => if val > 0.0 { val } else { 0.005 * val }
@@ -62,6 +74,34 @@ impl ActivationFunction {
     }
 }
 
+impl FromStr for ActivationFunction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "sigmoid" => Ok(Self::Sigmoid),
+            "tanh" => Ok(Self::Tanh),
+            "sin" => Ok(Self::Sin),
+            "gauss" => Ok(Self::Gauss),
+            "relu" => Ok(Self::Relu),
+            "elu" => Ok(Self::Elu),
+            "lelu" => Ok(Self::Lelu),
+            "selu" => Ok(Self::Selu),
+            "softplus" => Ok(Self::SoftPlus),
+            "identity" => Ok(Self::Identity),
+            "clamped" => Ok(Self::Clamped),
+            "inv" => Ok(Self::Inv),
+            "log" => Ok(Self::Log),
+            "exp" => Ok(Self::Exp),
+            "abs" => Ok(Self::Abs),
+            "hat" => Ok(Self::Hat),
+            "square" => Ok(Self::Square),
+            "cube" => Ok(Self::Cube),
+            _ => Err(format!("unknown activation function `{}`", s)),
+        }
+    }
+}
+
 impl Distribution<ActivationFunction> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> ActivationFunction {
         match rng.gen_range(0..=17) {
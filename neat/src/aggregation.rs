@@ -1,7 +1,12 @@
+use std::str::FromStr;
+
 use rand::{distributions::{Distribution, Standard}, Rng};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum AggregationFunction {
+/// Serialized under the lowercase names neat-python uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AggregationFunction {
     Product,
     Sum,
     Max,
@@ -11,10 +16,15 @@ pub(crate) enum AggregationFunction {
 }
 
 impl AggregationFunction {
+    pub const ALL: [Self; 6] = [
+        Self::Product, Self::Sum, Self::Max, Self::Min, Self::MaxAbs, 
+        Self::Mean,
+    ];
+
     pub fn aggregate(&self, vals: &[f64]) -> f64 {
         match self {
-            Self::Product => vals.into_iter().fold(1.0, |acc, x| acc * x),
-            Self::Sum => vals.into_iter().sum(),
+            Self::Product => vals.iter().fold(1.0, |acc, x| acc * x),
+            Self::Sum => vals.iter().sum(),
             Self::Max => {
                 if vals.is_empty() { return 0.0; }
                 let mut x = vals[0];
@@ -33,7 +43,26 @@ impl AggregationFunction {
                 for &y in vals { if y.abs() > x { x = y; } }
                 x
             }
-            Self::Mean => vals.into_iter().sum::<f64>() / (vals.len() as f64),
+            Self::Mean => {
+                if vals.is_empty() { return 0.0; }
+                vals.iter().sum::<f64>() / (vals.len() as f64)
+            }
+        }
+    }
+}
+
+impl FromStr for AggregationFunction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "product" => Ok(Self::Product),
+            "sum" => Ok(Self::Sum),
+            "max" => Ok(Self::Max),
+            "min" => Ok(Self::Min),
+            "maxabs" => Ok(Self::MaxAbs),
+            "mean" => Ok(Self::Mean),
+            _ => Err(format!("unknown aggregation function `{}`", s)),
         }
     }
 }
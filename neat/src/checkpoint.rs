@@ -0,0 +1,343 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{genome::Genome, population::Population};
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Serde(serde_json::Error),
+    /// JSON has no representation for NaN and infinities, so a population
+    /// holding one cannot be saved. Names the offending value.
+    NonFinite(String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "checkpoint I/O error: {}", e),
+            Self::Serde(e) => write!(f, "malformed checkpoint: {}", e),
+            Self::NonFinite(what) => 
+                write!(f, "cannot save non-finite {}", what),
+        }
+    }
+}
+
+impl Error for CheckpointError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Serde(e) => Some(e),
+            Self::NonFinite(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for CheckpointError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for CheckpointError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Serde(e)
+    }
+}
+
+/// Writes the state of a population to disk every `generation_interval`
+/// generations and/or every `time_interval`, whichever comes first.
+///
+/// Checkpoints are named `<prefix><generation>` and hold everything needed
+/// to continue the run with [`Population::restore`]: genomes, species and
+/// their stagnation history, innovation counters, the generation number and
+/// the state of the random number generator.
+#[derive(Debug, Clone)]
+pub struct Checkpointer {
+    generation_interval: Option<usize>,
+    time_interval:       Option<Duration>,
+    prefix:              PathBuf,
+
+    last_generation: usize,
+    last_time:       Instant,
+}
+
+impl Checkpointer {
+    pub fn new<P: Into<PathBuf>>(generation_interval: Option<usize>,
+            time_interval: Option<Duration>, prefix: P) -> Self {
+        Self {
+            generation_interval,
+            time_interval,
+            prefix: prefix.into(),
+            last_generation: 0,
+            last_time: Instant::now(),
+        }
+    }
+
+    /// Path of the checkpoint for `generation`.
+    pub fn path(&self, generation: usize) -> PathBuf {
+        let mut name = self.prefix.clone().into_os_string();
+        name.push(generation.to_string());
+        name.into()
+    }
+
+    /// Called once the population has been bred for its next generation.
+    /// Saves a checkpoint if one of the intervals has passed and returns its
+    /// path.
+    pub fn end_generation(&mut self, population: &Population) 
+            -> Result<Option<PathBuf>, CheckpointError> {
+        let generation = population.get_generation();
+        // a checkpointer reused on an earlier, restored population starts
+        // counting from there
+        if generation < self.last_generation {
+            self.last_generation = generation;
+        }
+        let due_generation = self.generation_interval
+            .is_some_and(|n| generation - self.last_generation >= n);
+        let due_time = self.time_interval
+            .is_some_and(|t| self.last_time.elapsed() >= t);
+        if !due_generation && !due_time {
+            return Ok(None);
+        }
+
+        let path = self.path(generation);
+        population.save(&path)?;
+        self.last_generation = generation;
+        self.last_time = Instant::now();
+        Ok(Some(path))
+    }
+}
+
+/// Name of the first float in `genome` that is not finite.
+fn find_non_finite_gene(genome: &Genome) -> Option<String> {
+    let id = genome.get_id();
+    if !genome.get_fitness().is_finite() {
+        return Some(format!("fitness {} of genome {}", genome.get_fitness(),
+            id));
+    }
+
+    let mut nodes: Vec<_> = genome.nodes().values().collect();
+    nodes.sort_by_key(|n| n.get_id());
+    for node in nodes {
+        let (a, b, c, d) = node.get_izhikevich_params();
+        let values = [node.get_bias(), node.get_response(), 
+            node.get_time_constant(), a, b, c, d];
+        if values.iter().any(|v| !v.is_finite()) {
+            return Some(format!("attribute of node {} in genome {}", 
+                node.get_id(), id));
+        }
+    }
+
+    let mut conns: Vec<_> = genome.connections().values().collect();
+    conns.sort_by_key(|c| c.get_id());
+    conns.into_iter()
+        .find(|c| !c.get_weight().is_finite())
+        .map(|c| format!("weight of connection {} in genome {}", c.get_id(),
+            id))
+}
+
+/// Name of the first value in `population` that would not survive a round
+/// trip through JSON.
+fn find_non_finite(population: &Population) -> Option<String> {
+    let config = population.config().float_values();
+    if let Some((key, value)) = config.iter().find(|(_, v)| !v.is_finite()) {
+        return Some(format!("value {} of `{}` in the config", value, key));
+    }
+
+    let mut ids: Vec<usize> = population.genomes().keys().copied().collect();
+    ids.sort_unstable();
+    let genomes = ids.iter().map(|id| &population.genomes()[id])
+        .chain(population.best_genome());
+    for genome in genomes {
+        if let Some(what) = find_non_finite_gene(genome) {
+            return Some(what);
+        }
+    }
+
+    let species = population.species().species();
+    let mut keys: Vec<usize> = species.keys().copied().collect();
+    keys.sort_unstable();
+    for key in keys {
+        let s = &species[&key];
+        let fitnesses = s.get_fitness().into_iter()
+            .chain(s.get_adjusted_fitness())
+            .chain(s.fitness_history().iter().copied());
+        for fitness in fitnesses {
+            if !fitness.is_finite() {
+                return Some(format!("fitness {} of species {}", fitness, 
+                    key));
+            }
+        }
+        if let Some(what) = find_non_finite_gene(s.representative()) {
+            return Some(format!("{} representing species {}", what, key));
+        }
+    }
+    None
+}
+
+/// Writes `population` as JSON to `path`, going through a temporary file so
+/// that an interrupted write never leaves a truncated checkpoint behind.
+/// Populations holding NaN or infinite values are rejected before anything
+/// is written, as they could not be restored.
+pub(crate) fn save(population: &Population, path: &Path) 
+        -> Result<(), CheckpointError> {
+    if let Some(what) = find_non_finite(population) {
+        return Err(CheckpointError::NonFinite(what));
+    }
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    serde_json::to_writer(&mut writer, population)?;
+    writer.flush()?;
+    drop(writer);
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+pub(crate) fn restore(path: &Path) -> Result<Population, CheckpointError> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{config::NeatConfig, reporting::Reporter};
+
+    fn config() -> NeatConfig {
+        NeatConfig {
+            seed: Some(3),
+            num_inputs: 2,
+            num_outputs: 1,
+            pop_size: 30,
+            no_fitness_termination: true,
+            ..NeatConfig::default()
+        }
+    }
+
+    fn fitness(genomes: &mut [&mut Genome], _: &NeatConfig) {
+        for genome in genomes.iter_mut() {
+            let mut weights: Vec<f64> = genome.connections().values()
+                .map(|c| c.get_weight())
+                .collect();
+            weights.sort_by(f64::total_cmp);
+            let weights: f64 = weights.iter().sum();
+            genome.set_fitness(weights + genome.nodes().len() as f64);
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("neat-checkpoint-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn restored_population_continues_the_run() {
+        let path = temp_path("continue");
+        let mut population = Population::new(config());
+        population.run(fitness, Some(3));
+        population.save(&path).unwrap();
+
+        let mut restored = Population::restore(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        population.run(fitness, Some(3));
+        restored.run(fitness, Some(3));
+        assert_eq!(population.get_generation(), restored.get_generation());
+        assert_eq!(population.genomes(), restored.genomes());
+        assert_eq!(population.species(), restored.species());
+        assert_eq!(population.best_genome(), restored.best_genome());
+    }
+
+    #[test]
+    fn non_finite_fitness_is_rejected() {
+        let path = temp_path("nan");
+        let mut population = Population::new(config());
+        population.run(|genomes: &mut [&mut Genome], _: &NeatConfig| {
+            for genome in genomes.iter_mut() {
+                genome.set_fitness(f64::NEG_INFINITY);
+            }
+        }, Some(1));
+
+        let result = population.save(&path);
+        assert!(matches!(result, Err(CheckpointError::NonFinite(_))), 
+            "{:?}", result);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn non_finite_config_is_rejected() {
+        let config = NeatConfig {
+            fitness_threshold: f64::INFINITY,
+            ..config()
+        };
+        let result = Population::new(config).save(temp_path("config"));
+        assert!(matches!(&result, Err(CheckpointError::NonFinite(what))
+                if what.contains("fitness_threshold")), 
+            "{:?}", result);
+    }
+
+    #[test]
+    fn checkpointer_can_be_reused_on_an_earlier_population() {
+        let mut checkpointer = Checkpointer::new(Some(2), None, 
+            temp_path("reused-"));
+        let mut population = Population::new(config());
+        population.run(fitness, Some(4));
+        let path = checkpointer.end_generation(&population).unwrap().unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let mut earlier = Population::new(config());
+        assert_eq!(checkpointer.end_generation(&earlier).unwrap(), None);
+        earlier.run(fitness, Some(2));
+        let path = checkpointer.end_generation(&earlier).unwrap().unwrap();
+        assert_eq!(path, checkpointer.path(2));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[derive(Default)]
+    struct Checkpoints {
+        saved:  Vec<usize>,
+        failed: Vec<usize>,
+    }
+
+    impl Reporter for Checkpoints {
+        fn checkpoint_saved(&mut self, generation: usize, path: &Path) {
+            assert!(path.exists());
+            std::fs::remove_file(path).unwrap();
+            self.saved.push(generation);
+        }
+
+        fn checkpoint_failed(&mut self, generation: usize, 
+                error: &CheckpointError) {
+            assert!(matches!(error, CheckpointError::Io(_)));
+            self.failed.push(generation);
+        }
+    }
+
+    #[test]
+    fn checkpoints_are_reported() {
+        let checkpoints = Arc::new(Mutex::new(Checkpoints::default()));
+        let mut population = Population::new(config());
+        population.add_reporter(checkpoints.clone());
+        population.set_checkpointer(Checkpointer::new(Some(2), None, 
+            temp_path("every-other-")));
+        population.run(fitness, Some(5));
+        assert_eq!(checkpoints.lock().unwrap().saved, [2, 4]);
+
+        let checkpoints = Arc::new(Mutex::new(Checkpoints::default()));
+        population.add_reporter(checkpoints.clone());
+        population.set_checkpointer(Checkpointer::new(Some(1), None, 
+            temp_path("missing").join("dir").join("gen-")));
+        population.run(fitness, Some(2));
+        assert_eq!(checkpoints.lock().unwrap().failed, [6, 7]);
+    }
+}
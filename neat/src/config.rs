@@ -1,42 +1,1428 @@
-pub const FITNESS_THRESH: f64 = 3.9;
-pub const POP_SIZE: usize     = 150;
+use std::{fmt, fs, io, path::Path, str::FromStr};
 
-pub const COMPAT_DISJOINT_COEFFICIENT: f64 = 1.0;
-pub const COMPAT_WEIGHT_COEFFICIENT: f64   = 1.0;
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
 
-pub const ACTIVATION_MUT_PROB: f64  = 0.2;
-pub const AGGREGATION_MUT_PROB: f64 = 0.2;
+use crate::{activation::ActivationFunction, aggregation::AggregationFunction};
 
-pub const BIAS_MUTATE_POWER: f64 = 0.5;
-pub const BIAS_MUTATE_RATE: f64  = 0.7;
-pub const BIAS_REPLACE_RATE: f64 = 0.1;
-pub const BIAS_MAX_VALUE: f64    = 30.0;
-pub const BIAS_MIN_VALUE: f64    = -30.0;
+/// Sections of a neat-python configuration file that are understood by the
+/// loader. Keys may appear in any of them.
+const SECTIONS: [&str; 5] = [
+    "NEAT",
+    "DefaultGenome",
+    "DefaultSpeciesSet",
+    "DefaultStagnation",
+    "DefaultReproduction",
+];
 
-pub const CONN_ADD_PROB: f64 = 0.2;
-pub const CONN_DEL_PROB: f64 = 0.2;
-pub const NODE_ADD_PROB: f64 = 0.1;
-pub const NODE_DEL_PROB: f64 = 0.1;
+/// Error raised while loading or validating a [`NeatConfig`].
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    /// The file is not well formed INI, TOML or JSON.
+    Syntax { line: Option<usize>, message: String },
+    UnknownSection { section: String, line: usize },
+    UnknownKey { key: String, line: Option<usize> },
+    InvalidValue { key: String, line: Option<usize>, message: String },
+}
 
-pub const ENABLE_PROB: f64 = 0.02;
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line = |f: &mut fmt::Formatter<'_>, line: &Option<usize>| {
+            match line {
+                Some(l) => write!(f, "line {}: ", l),
+                None => Ok(()),
+            }
+        };
 
-pub const WEIGHT_MUTATE_POWER: f64 = 0.5;
-pub const WEIGHT_MUTATE_RATE: f64  = 0.8;
-pub const WEIGHT_REPLACE_RATE: f64 = 0.1;
-pub const WEIGHT_MAX_VALUE: f64    = 30.0;
-pub const WEIGHT_MIN_VALUE: f64    = -30.0;
+        match self {
+            Self::Io(e) => write!(f, "could not read config: {}", e),
+            Self::Syntax { line: l, message } => {
+                line(f, l)?;
+                write!(f, "syntax error: {}", message)
+            }
+            Self::UnknownSection { section, line: l } => {
+                write!(f, "line {}: unknown section `{}`", l, section)
+            }
+            Self::UnknownKey { key, line: l } => {
+                line(f, l)?;
+                write!(f, "unknown key `{}`", key)
+            }
+            Self::InvalidValue { key, line: l, message } => {
+                line(f, l)?;
+                write!(f, "invalid value for `{}`: {}", key, message)
+            }
+        }
+    }
+}
 
-pub const RESPONSE_REPLACE_RATE: f64 = 0.1;
-pub const RESPONSE_MUTATE_RATE: f64  = 0.1;
-pub const RESPONSE_MUTATE_POWER: f64 = 0.1;
-pub const RESPONSE_MAX_VALUE: f64    = 30.0;
-pub const RESPONSE_MIN_VALUE: f64    = -30.0;
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
-pub const COMPATIBILITY_THRESH: f64 = 2.0;
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
 
-pub const MAX_STAGNATION: f64 = 15.0;
+/// How the connections of a freshly created genome are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum InitialConnection {
+    Unconnected,
+    /// One randomly chosen input is connected to all outputs.
+    FsNeatNoHidden,
+    /// One randomly chosen input is connected to all hidden and output 
+    /// nodes.
+    FsNeatHidden,
+    /// Inputs are connected to the hidden nodes and the hidden nodes to the
+    /// outputs. Without hidden nodes the inputs connect to the outputs.
+    FullNoDirect,
+    /// Like [`InitialConnection::FullNoDirect`], with additional direct 
+    /// input to output connections.
+    FullDirect,
+    /// Each connection of `FullNoDirect` is present with the given
+    /// probability.
+    PartialNoDirect(f64),
+    /// Each connection of `FullDirect` is present with the given probability.
+    PartialDirect(f64),
+}
 
-pub const ELITISM: f64         = 2.0;
-pub const SURVIVAL_THRESH: f64 = 0.2;
+impl FromStr for InitialConnection {
+    type Err = String;
 
-pub const SINGLE_MUTATION: bool = true;
\ No newline at end of file
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let name = parts.next().unwrap_or("");
+        let mut prob = || -> Result<f64, String> {
+            let p = parts.next()
+                .ok_or(format!("`{}` needs a connection probability", name))?;
+            parse(p)
+        };
+
+        let ret = match name {
+            "unconnected" => Self::Unconnected,
+            "fs_neat" | "fs_neat_nohidden" => Self::FsNeatNoHidden,
+            "fs_neat_hidden" => Self::FsNeatHidden,
+            "full" | "full_nodirect" => Self::FullNoDirect,
+            "full_direct" => Self::FullDirect,
+            "partial" | "partial_nodirect" => Self::PartialNoDirect(prob()?),
+            "partial_direct" => Self::PartialDirect(prob()?),
+            _ => return Err(format!("unknown connection type `{}`", s)),
+        };
+        if parts.next().is_some() {
+            return Err(format!("trailing values in `{}`", s));
+        }
+        Ok(ret)
+    }
+}
+
+impl fmt::Display for InitialConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unconnected => write!(f, "unconnected"),
+            Self::FsNeatNoHidden => write!(f, "fs_neat_nohidden"),
+            Self::FsNeatHidden => write!(f, "fs_neat_hidden"),
+            Self::FullNoDirect => write!(f, "full_nodirect"),
+            Self::FullDirect => write!(f, "full_direct"),
+            Self::PartialNoDirect(p) => write!(f, "partial_nodirect {}", p),
+            Self::PartialDirect(p) => write!(f, "partial_direct {}", p),
+        }
+    }
+}
+
+impl TryFrom<String> for InitialConnection {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<InitialConnection> for String {
+    fn from(c: InitialConnection) -> Self {
+        c.to_string()
+    }
+}
+
+/// Distribution new attribute values are drawn from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InitType {
+    /// Normal distribution around the configured mean.
+    Gaussian,
+    /// Uniform distribution covering two standard deviations around the mean.
+    Uniform,
+}
+
+impl FromStr for InitType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "gaussian" | "normal" => Ok(Self::Gaussian),
+            "uniform" => Ok(Self::Uniform),
+            _ => Err(format!("unknown distribution `{}`", s)),
+        }
+    }
+}
+
+/// Reduces the fitness values of a group of genomes to a single value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FitnessFunction {
+    Max,
+    Min,
+    Mean,
+    Median,
+}
+
+impl FitnessFunction {
+    pub fn apply(&self, vals: &[f64]) -> f64 {
+        if vals.is_empty() { return 0.0; }
+        match self {
+            Self::Max => vals.iter().copied().fold(f64::MIN, f64::max),
+            Self::Min => vals.iter().copied().fold(f64::MAX, f64::min),
+            Self::Mean => vals.iter().sum::<f64>() / vals.len() as f64,
+            Self::Median => {
+                let mut sorted = vals.to_vec();
+                sorted.sort_by(f64::total_cmp);
+                let mid = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) {
+                    (sorted[mid - 1] + sorted[mid]) / 2.0
+                } else {
+                    sorted[mid]
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for FitnessFunction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "max" => Ok(Self::Max),
+            "min" => Ok(Self::Min),
+            "mean" => Ok(Self::Mean),
+            "median" => Ok(Self::Median),
+            _ => Err(format!("unknown fitness function `{}`", s)),
+        }
+    }
+}
+
+/// Serde representation of optional values that neat-python spells as a
+/// keyword when absent, e.g. `activation_default = random`.
+mod keyword_or {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr<T> {
+        Value(T),
+        Keyword(String),
+    }
+
+    pub fn serialize<S, T>(value: &Option<T>, keyword: &str, serializer: S)
+            -> Result<S::Ok, S::Error> where S: Serializer, T: Serialize {
+        match value {
+            Some(v) => v.serialize(serializer),
+            None => serializer.serialize_str(keyword),
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(keywords: &[&str], deserializer: D) 
+            -> Result<Option<T>, D::Error> 
+            where D: Deserializer<'de>, T: Deserialize<'de> {
+        match Repr::deserialize(deserializer)? {
+            Repr::Value(v) => Ok(Some(v)),
+            Repr::Keyword(k) if keywords.contains(&k.to_lowercase().as_str())
+                => Ok(None),
+            Repr::Keyword(k) => Err(D::Error::custom(
+                format!("unknown value `{}`", k))),
+        }
+    }
+}
+
+/// `random` stands for `None`.
+mod random_or {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S, T>(value: &Option<T>, serializer: S)
+            -> Result<S::Ok, S::Error> where S: Serializer, T: Serialize {
+        super::keyword_or::serialize(value, "random", serializer)
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) 
+            -> Result<Option<T>, D::Error> 
+            where D: Deserializer<'de>, T: Deserialize<'de> {
+        super::keyword_or::deserialize(&["random", "none"], deserializer)
+    }
+}
+
+/// `default` stands for `None`.
+mod default_or {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S, T>(value: &Option<T>, serializer: S)
+            -> Result<S::Ok, S::Error> where S: Serializer, T: Serialize {
+        super::keyword_or::serialize(value, "default", serializer)
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) 
+            -> Result<Option<T>, D::Error> 
+            where D: Deserializer<'de>, T: Deserialize<'de> {
+        super::keyword_or::deserialize(&["default", "none"], deserializer)
+    }
+}
+
+/// Lists of names, given either as an array or, like in neat-python, as a
+/// single space separated string.
+mod name_list {
+    use std::{fmt, str::FromStr};
+
+    use serde::{de::Error, Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr<T> {
+        List(Vec<T>),
+        Text(String),
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+            where D: Deserializer<'de>, T: Deserialize<'de> + FromStr, 
+            T::Err: fmt::Display {
+        match Repr::deserialize(deserializer)? {
+            Repr::List(v) => Ok(v),
+            Repr::Text(s) => super::parse_list(&s).map_err(D::Error::custom),
+        }
+    }
+}
+
+/// All tunable NEAT hyperparameters.
+///
+/// Every genetic operator takes a reference to one of these, so experiments
+/// with different settings can run side by side in the same process. The
+/// field names follow the keys used by neat-python configuration files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NeatConfig {
+    pub fitness_threshold:   f64,
+    /// How the fitness values of all genomes are reduced to the value that
+    /// is compared to `fitness_threshold`.
+    pub fitness_criterion:   FitnessFunction,
+    /// Whether to keep running after reaching `fitness_threshold`.
+    pub no_fitness_termination: bool,
+    pub pop_size:            usize,
+    pub reset_on_extinction: bool,
+    /// Seed of the random number generator of a population. Runs with the
+    /// same seed and configuration are identical; without one the generator
+    /// is seeded from the operating system.
+    pub seed:                Option<u64>,
+
+    pub num_inputs:  usize,
+    pub num_outputs: usize,
+    pub num_hidden:  usize,
+    /// Whether connections that create cycles are forbidden. Recurrent
+    /// genomes may also contain self-connections.
+    pub feed_forward: bool,
+
+    pub initial_connection: InitialConnection,
+
+    pub compatibility_disjoint_coefficient: f64,
+    pub compatibility_weight_coefficient:   f64,
+
+    /// Activation function of new nodes, picked from `activation_options`
+    /// at random if `None`.
+    #[serde(with = "random_or")]
+    pub activation_default:      Option<ActivationFunction>,
+    /// Activation functions nodes may be created with or mutate to.
+    #[serde(deserialize_with = "name_list::deserialize")]
+    pub activation_options:      Vec<ActivationFunction>,
+    pub activation_mutate_rate:  f64,
+    #[serde(with = "random_or")]
+    pub aggregation_default:     Option<AggregationFunction>,
+    #[serde(deserialize_with = "name_list::deserialize")]
+    pub aggregation_options:     Vec<AggregationFunction>,
+    pub aggregation_mutate_rate: f64,
+
+    pub bias_init_mean:    f64,
+    pub bias_init_stdev:   f64,
+    pub bias_init_type:    InitType,
+    pub bias_mutate_power: f64,
+    pub bias_mutate_rate:  f64,
+    pub bias_replace_rate: f64,
+    pub bias_max_value:    f64,
+    pub bias_min_value:    f64,
+
+    pub conn_add_prob:    f64,
+    pub conn_delete_prob: f64,
+    pub node_add_prob:    f64,
+    pub node_delete_prob: f64,
+
+    /// Whether new connections are enabled, random if `None`.
+    #[serde(with = "random_or")]
+    pub enabled_default:           Option<bool>,
+    pub enabled_mutate_rate:       f64,
+    /// Added to `enabled_mutate_rate` for disabled connections.
+    pub enabled_rate_to_true_add:  f64,
+    /// Added to `enabled_mutate_rate` for enabled connections.
+    pub enabled_rate_to_false_add: f64,
+
+    pub weight_init_mean:    f64,
+    pub weight_init_stdev:   f64,
+    pub weight_init_type:    InitType,
+    pub weight_mutate_power: f64,
+    pub weight_mutate_rate:  f64,
+    pub weight_replace_rate: f64,
+    pub weight_max_value:    f64,
+    pub weight_min_value:    f64,
+
+    pub response_init_mean:    f64,
+    pub response_init_stdev:   f64,
+    pub response_init_type:    InitType,
+    pub response_replace_rate: f64,
+    pub response_mutate_rate:  f64,
+    pub response_mutate_power: f64,
+    pub response_max_value:    f64,
+    pub response_min_value:    f64,
+
+    pub time_constant_init_mean:    f64,
+    pub time_constant_init_stdev:   f64,
+    pub time_constant_init_type:    InitType,
+    pub time_constant_replace_rate: f64,
+    pub time_constant_mutate_rate:  f64,
+    pub time_constant_mutate_power: f64,
+    pub time_constant_max_value:    f64,
+    pub time_constant_min_value:    f64,
+
+    // parameters of Izhikevich neurons, see `NodeGene::get_izhikevich_params`
+    pub a_init_mean:    f64,
+    pub a_init_stdev:   f64,
+    pub a_init_type:    InitType,
+    pub a_replace_rate: f64,
+    pub a_mutate_rate:  f64,
+    pub a_mutate_power: f64,
+    pub a_max_value:    f64,
+    pub a_min_value:    f64,
+
+    pub b_init_mean:    f64,
+    pub b_init_stdev:   f64,
+    pub b_init_type:    InitType,
+    pub b_replace_rate: f64,
+    pub b_mutate_rate:  f64,
+    pub b_mutate_power: f64,
+    pub b_max_value:    f64,
+    pub b_min_value:    f64,
+
+    pub c_init_mean:    f64,
+    pub c_init_stdev:   f64,
+    pub c_init_type:    InitType,
+    pub c_replace_rate: f64,
+    pub c_mutate_rate:  f64,
+    pub c_mutate_power: f64,
+    pub c_max_value:    f64,
+    pub c_min_value:    f64,
+
+    pub d_init_mean:    f64,
+    pub d_init_stdev:   f64,
+    pub d_init_type:    InitType,
+    pub d_replace_rate: f64,
+    pub d_mutate_rate:  f64,
+    pub d_mutate_power: f64,
+    pub d_max_value:    f64,
+    pub d_min_value:    f64,
+
+    pub compatibility_threshold: f64,
+    /// Number of species the compatibility threshold is steered towards.
+    /// Zero keeps the threshold fixed.
+    pub target_num_species:               usize,
+    pub compatibility_threshold_modifier: f64,
+    pub compatibility_threshold_min:      f64,
+
+    pub species_fitness_func: FitnessFunction,
+    pub max_stagnation:       usize,
+    /// Number of best species that are protected from stagnation.
+    pub species_elitism:      usize,
+
+    pub elitism:            usize,
+    pub survival_threshold: f64,
+    pub min_species_size:   usize,
+
+    pub single_structural_mutation: bool,
+    /// Whether a structural mutation that cannot be applied is replaced by 
+    /// a related one, e.g. adding a connection instead of splitting one in
+    /// a genome without connections. `None` follows 
+    /// `single_structural_mutation`.
+    #[serde(with = "default_or")]
+    pub structural_mutation_surer:  Option<bool>,
+    /// Whether innovation numbers are only shared by identical mutations
+    /// within the same generation, as in the original NEAT, or for the whole
+    /// run.
+    pub reset_innovations:          bool,
+}
+
+impl Default for NeatConfig {
+    fn default() -> Self {
+        Self {
+            fitness_threshold:   3.9,
+            fitness_criterion:   FitnessFunction::Max,
+            no_fitness_termination: false,
+            pop_size:            150,
+            reset_on_extinction: true,
+            seed:                None,
+
+            num_inputs:  4,
+            num_outputs: 1,
+            num_hidden:  0,
+            feed_forward: true,
+
+            initial_connection: InitialConnection::FullNoDirect,
+
+            compatibility_disjoint_coefficient: 1.0,
+            compatibility_weight_coefficient:   1.0,
+
+            activation_default:      Some(ActivationFunction::Sigmoid),
+            activation_options:      ActivationFunction::ALL.to_vec(),
+            activation_mutate_rate:  0.2,
+            aggregation_default:     Some(AggregationFunction::Sum),
+            aggregation_options:     AggregationFunction::ALL.to_vec(),
+            aggregation_mutate_rate: 0.2,
+
+            bias_init_mean:    0.0,
+            bias_init_stdev:   1.0,
+            bias_init_type:    InitType::Gaussian,
+            bias_mutate_power: 0.5,
+            bias_mutate_rate:  0.7,
+            bias_replace_rate: 0.1,
+            bias_max_value:    30.0,
+            bias_min_value:    -30.0,
+
+            conn_add_prob:    0.2,
+            conn_delete_prob: 0.2,
+            node_add_prob:    0.1,
+            node_delete_prob: 0.1,
+
+            enabled_default:           Some(true),
+            enabled_mutate_rate:       0.02,
+            enabled_rate_to_true_add:  0.0,
+            enabled_rate_to_false_add: 0.0,
+
+            weight_init_mean:    0.0,
+            weight_init_stdev:   1.0,
+            weight_init_type:    InitType::Gaussian,
+            weight_mutate_power: 0.5,
+            weight_mutate_rate:  0.8,
+            weight_replace_rate: 0.1,
+            weight_max_value:    30.0,
+            weight_min_value:    -30.0,
+
+            response_init_mean:    1.0,
+            response_init_stdev:   0.0,
+            response_init_type:    InitType::Gaussian,
+            response_replace_rate: 0.1,
+            response_mutate_rate:  0.1,
+            response_mutate_power: 0.1,
+            response_max_value:    30.0,
+            response_min_value:    -30.0,
+
+            time_constant_init_mean:    1.0,
+            time_constant_init_stdev:   0.0,
+            time_constant_init_type:    InitType::Gaussian,
+            time_constant_replace_rate: 0.0,
+            time_constant_mutate_rate:  0.0,
+            time_constant_mutate_power: 0.1,
+            time_constant_max_value:    10.0,
+            time_constant_min_value:    0.01,
+
+            // regular spiking neuron
+            a_init_mean:    0.02,
+            a_init_stdev:   0.0,
+            a_init_type:    InitType::Gaussian,
+            a_replace_rate: 0.0,
+            a_mutate_rate:  0.0,
+            a_mutate_power: 0.01,
+            a_max_value:    1.0,
+            a_min_value:    0.0,
+
+            b_init_mean:    0.2,
+            b_init_stdev:   0.0,
+            b_init_type:    InitType::Gaussian,
+            b_replace_rate: 0.0,
+            b_mutate_rate:  0.0,
+            b_mutate_power: 0.01,
+            b_max_value:    1.0,
+            b_min_value:    0.0,
+
+            c_init_mean:    -65.0,
+            c_init_stdev:   0.0,
+            c_init_type:    InitType::Gaussian,
+            c_replace_rate: 0.0,
+            c_mutate_rate:  0.0,
+            c_mutate_power: 1.0,
+            c_max_value:    -40.0,
+            c_min_value:    -80.0,
+
+            d_init_mean:    8.0,
+            d_init_stdev:   0.0,
+            d_init_type:    InitType::Gaussian,
+            d_replace_rate: 0.0,
+            d_mutate_rate:  0.0,
+            d_mutate_power: 0.1,
+            d_max_value:    10.0,
+            d_min_value:    0.0,
+
+            compatibility_threshold: 2.0,
+            target_num_species:               0,
+            compatibility_threshold_modifier: 0.1,
+            compatibility_threshold_min:      0.1,
+
+            species_fitness_func: FitnessFunction::Max,
+            max_stagnation:       15,
+            species_elitism:      0,
+
+            elitism:            2,
+            survival_threshold: 0.2,
+            min_species_size:   2,
+
+            single_structural_mutation: true,
+            structural_mutation_surer:  None,
+            reset_innovations:          true,
+        }
+    }
+}
+
+fn parse<T: FromStr>(value: &str) -> Result<T, String>
+        where T::Err: fmt::Display {
+    value.trim().parse().map_err(|e: T::Err| format!("`{}`: {}", value, e))
+}
+
+fn parse_optional<T: FromStr>(value: &str) -> Result<Option<T>, String>
+        where T::Err: fmt::Display {
+    match value.trim().to_lowercase().as_str() {
+        "" | "none" => Ok(None),
+        _ => parse(value).map(Some),
+    }
+}
+
+/// Like [`parse_optional`], with neat-python's `random` for `None`.
+fn parse_random<T: FromStr>(value: &str) -> Result<Option<T>, String>
+        where T::Err: fmt::Display {
+    match value.trim().to_lowercase().as_str() {
+        "random" | "none" => Ok(None),
+        _ => parse(value).map(Some),
+    }
+}
+
+fn parse_list<T: FromStr>(value: &str) -> Result<Vec<T>, String>
+        where T::Err: fmt::Display {
+    value.split_whitespace().map(parse).collect()
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(format!("`{}` is not a boolean", value)),
+    }
+}
+
+/// A boolean, or `None` if `value` is `keyword` or `none`.
+fn parse_bool_or(value: &str, keyword: &str) -> Result<Option<bool>, String> {
+    match value.trim().to_lowercase().as_str() {
+        "none" => Ok(None),
+        v if v == keyword => Ok(None),
+        _ => parse_bool(value).map(Some),
+    }
+}
+
+impl NeatConfig {
+    /// Whether structural mutations that cannot be applied are replaced by
+    /// related ones, see `structural_mutation_surer`.
+    #[inline]
+    pub fn check_structural_mutation_surer(&self) -> bool {
+        self.structural_mutation_surer
+            .unwrap_or(self.single_structural_mutation)
+    }
+
+    /// Loads a configuration file, picking the format from the extension:
+    /// `.toml` and `.json` are read through serde, everything else is
+    /// treated as a neat-python style INI file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml_str(&text),
+            Some("json") => Self::from_json_str(&text),
+            _ => Self::from_ini_str(&text),
+        }
+    }
+
+    /// Parses a neat-python configuration file. Keys are looked up in all
+    /// known sections; keys that are missing keep their default value.
+    pub fn from_ini_str(text: &str) -> Result<Self, ConfigError> {
+        let mut ret = Self::default();
+        let mut lines: HashMap<String, usize> = HashMap::new();
+        let mut entries: Vec<(String, String, usize)> = Vec::new();
+        let mut in_section = false;
+
+        for (idx, raw) in text.lines().enumerate() {
+            let line = idx + 1;
+            let trimmed = raw.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') || 
+                    trimmed.starts_with(';') {
+                continue;
+            }
+
+            if trimmed.starts_with('[') {
+                if !trimmed.ends_with(']') {
+                    return Err(ConfigError::Syntax {
+                        line: Some(line),
+                        message: format!("unterminated section `{}`", trimmed),
+                    });
+                }
+                let section = trimmed[1..trimmed.len() - 1].trim();
+                if !SECTIONS.contains(&section) {
+                    return Err(ConfigError::UnknownSection {
+                        section: section.to_string(),
+                        line,
+                    });
+                }
+                in_section = true;
+                continue;
+            }
+
+            // indented lines continue the value of the previous key
+            if raw.starts_with(char::is_whitespace) {
+                if let Some((_, value, _)) = entries.last_mut() {
+                    value.push(' ');
+                    value.push_str(trimmed);
+                    continue;
+                }
+            }
+
+            let (key, value) = match trimmed.find(['=', ':']) {
+                Some(pos) => (&trimmed[..pos], &trimmed[pos + 1..]),
+                None => return Err(ConfigError::Syntax {
+                    line: Some(line),
+                    message: format!("expected `key = value`, got `{}`", 
+                        trimmed),
+                }),
+            };
+            if !in_section {
+                return Err(ConfigError::Syntax {
+                    line: Some(line),
+                    message: "key outside of a section".to_string(),
+                });
+            }
+
+            let key = key.trim().to_lowercase();
+            if let Some(first) = lines.insert(key.clone(), line) {
+                return Err(ConfigError::Syntax {
+                    line: Some(line),
+                    message: format!("`{}` is already set on line {}", key, 
+                        first),
+                });
+            }
+            entries.push((key, value.trim().to_string(), line));
+        }
+
+        for (key, value, line) in entries {
+            ret.set(&key, &value).map_err(|e| match e {
+                ConfigError::UnknownKey { key, .. } => 
+                    ConfigError::UnknownKey { key, line: Some(line) },
+                ConfigError::InvalidValue { key, message, .. } => 
+                    ConfigError::InvalidValue { 
+                        key, 
+                        line: Some(line), 
+                        message,
+                    },
+                e => e,
+            })?;
+        }
+
+        ret.validate().map_err(|e| match e {
+            ConfigError::InvalidValue { line: None, key, message } => {
+                let line = lines.get(&key).copied();
+                ConfigError::InvalidValue { key, line, message }
+            }
+            e => e,
+        })?;
+        Ok(ret)
+    }
+
+    /// Parses a TOML document. Values may either be grouped into the same
+    /// sections as a neat-python file or be given at the top level.
+    pub fn from_toml_str(text: &str) -> Result<Self, ConfigError> {
+        let value: toml::Table = toml::from_str(text)
+            .map_err(|e| ConfigError::Syntax { 
+                line: None, 
+                message: e.to_string(),
+            })?;
+        let value = serde_json::to_value(value)
+            .map_err(|e| ConfigError::Syntax { 
+                line: None, 
+                message: e.to_string(),
+            })?;
+        Self::from_value(value)
+    }
+
+    /// Parses a JSON document laid out like the TOML one.
+    pub fn from_json_str(text: &str) -> Result<Self, ConfigError> {
+        let value = serde_json::from_str(text)
+            .map_err(|e: serde_json::Error| ConfigError::Syntax { 
+                line: Some(e.line()), 
+                message: e.to_string(),
+            })?;
+        Self::from_value(value)
+    }
+
+    fn from_value(value: serde_json::Value) -> Result<Self, ConfigError> {
+        let mut flat = serde_json::Map::new();
+        let root = match value {
+            serde_json::Value::Object(root) => root,
+            _ => return Err(ConfigError::Syntax {
+                line: None,
+                message: "expected a table at the top level".to_string(),
+            }),
+        };
+        for (key, value) in root {
+            match value {
+                serde_json::Value::Object(section) => {
+                    if !SECTIONS.contains(&key.as_str()) {
+                        return Err(ConfigError::UnknownKey { key, line: None });
+                    }
+                    flat.extend(section);
+                }
+                value => { flat.insert(key, value); }
+            }
+        }
+
+        if let Some(key) = flat.keys().find(|k| !Self::is_key(k)) {
+            return Err(ConfigError::UnknownKey { 
+                key: key.clone(), 
+                line: None,
+            });
+        }
+
+        // check the keys one at a time first so errors can name the key
+        for (key, value) in flat.iter() {
+            let mut single = serde_json::Map::new();
+            single.insert(key.clone(), value.clone());
+            serde_json::from_value::<Self>(serde_json::Value::Object(single))
+                .map_err(|e| ConfigError::InvalidValue {
+                    key: key.clone(),
+                    line: None,
+                    message: e.to_string(),
+                })?;
+        }
+
+        let ret: Self = serde_json::from_value(serde_json::Value::Object(flat))
+            .map_err(|e| ConfigError::Syntax { 
+                line: None, 
+                message: e.to_string(),
+            })?;
+        ret.validate()?;
+        Ok(ret)
+    }
+
+    fn is_key(key: &str) -> bool {
+        let mut probe = Self::default();
+        !matches!(probe.set(key, ""), Err(ConfigError::UnknownKey { .. }))
+    }
+
+    /// Sets a single value from its textual representation, as found in an
+    /// INI file.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let res = match key {
+            "fitness_threshold" => parse(value).map(|v| 
+                self.fitness_threshold = v),
+            "fitness_criterion" => parse(value).map(|v| 
+                self.fitness_criterion = v),
+            "no_fitness_termination" => parse_bool(value).map(|v| 
+                self.no_fitness_termination = v),
+            "pop_size" => parse(value).map(|v| self.pop_size = v),
+            "seed" => parse_optional(value).map(|v| self.seed = v),
+            "num_inputs" => parse(value).map(|v| self.num_inputs = v),
+            "num_outputs" => parse(value).map(|v| self.num_outputs = v),
+            "num_hidden" => parse(value).map(|v| self.num_hidden = v),
+            "feed_forward" => parse_bool(value).map(|v| 
+                self.feed_forward = v),
+            "initial_connection" => parse(value).map(|v| 
+                self.initial_connection = v),
+            "compatibility_disjoint_coefficient" => parse(value).map(|v| 
+                self.compatibility_disjoint_coefficient = v),
+            "compatibility_weight_coefficient" => parse(value).map(|v| 
+                self.compatibility_weight_coefficient = v),
+            "activation_default" => parse_random(value).map(|v| 
+                self.activation_default = v),
+            "activation_options" => parse_list(value).map(|v| 
+                self.activation_options = v),
+            "activation_mutate_rate" => parse(value).map(|v| 
+                self.activation_mutate_rate = v),
+            "aggregation_default" => parse_random(value).map(|v| 
+                self.aggregation_default = v),
+            "aggregation_options" => parse_list(value).map(|v| 
+                self.aggregation_options = v),
+            "aggregation_mutate_rate" => parse(value).map(|v| 
+                self.aggregation_mutate_rate = v),
+            "bias_init_mean" => parse(value).map(|v| self.bias_init_mean = v),
+            "bias_init_stdev" => parse(value).map(|v| self.bias_init_stdev = v),
+            "bias_init_type" => parse(value).map(|v| self.bias_init_type = v),
+            "bias_mutate_power" => parse(value).map(|v| 
+                self.bias_mutate_power = v),
+            "bias_mutate_rate" => parse(value).map(|v| 
+                self.bias_mutate_rate = v),
+            "bias_replace_rate" => parse(value).map(|v| 
+                self.bias_replace_rate = v),
+            "bias_max_value" => parse(value).map(|v| self.bias_max_value = v),
+            "bias_min_value" => parse(value).map(|v| self.bias_min_value = v),
+            "conn_add_prob" => parse(value).map(|v| self.conn_add_prob = v),
+            "conn_delete_prob" => parse(value).map(|v| 
+                self.conn_delete_prob = v),
+            "node_add_prob" => parse(value).map(|v| self.node_add_prob = v),
+            "node_delete_prob" => parse(value).map(|v| 
+                self.node_delete_prob = v),
+            "enabled_default" => parse_bool_or(value, "random").map(|v| 
+                self.enabled_default = v),
+            "enabled_mutate_rate" => parse(value).map(|v| 
+                self.enabled_mutate_rate = v),
+            "enabled_rate_to_true_add" => parse(value).map(|v| 
+                self.enabled_rate_to_true_add = v),
+            "enabled_rate_to_false_add" => parse(value).map(|v| 
+                self.enabled_rate_to_false_add = v),
+            "weight_init_mean" => parse(value).map(|v| 
+                self.weight_init_mean = v),
+            "weight_init_stdev" => parse(value).map(|v| 
+                self.weight_init_stdev = v),
+            "weight_init_type" => parse(value).map(|v| 
+                self.weight_init_type = v),
+            "weight_mutate_power" => parse(value).map(|v| 
+                self.weight_mutate_power = v),
+            "weight_mutate_rate" => parse(value).map(|v| 
+                self.weight_mutate_rate = v),
+            "weight_replace_rate" => parse(value).map(|v| 
+                self.weight_replace_rate = v),
+            "weight_max_value" => parse(value).map(|v| 
+                self.weight_max_value = v),
+            "weight_min_value" => parse(value).map(|v| 
+                self.weight_min_value = v),
+            "response_init_mean" => parse(value).map(|v| 
+                self.response_init_mean = v),
+            "response_init_stdev" => parse(value).map(|v| 
+                self.response_init_stdev = v),
+            "response_init_type" => parse(value).map(|v| 
+                self.response_init_type = v),
+            "response_replace_rate" => parse(value).map(|v| 
+                self.response_replace_rate = v),
+            "response_mutate_rate" => parse(value).map(|v| 
+                self.response_mutate_rate = v),
+            "response_mutate_power" => parse(value).map(|v| 
+                self.response_mutate_power = v),
+            "response_max_value" => parse(value).map(|v| 
+                self.response_max_value = v),
+            "response_min_value" => parse(value).map(|v| 
+                self.response_min_value = v),
+            "time_constant_init_mean" => parse(value).map(|v| 
+                self.time_constant_init_mean = v),
+            "time_constant_init_stdev" => parse(value).map(|v| 
+                self.time_constant_init_stdev = v),
+            "time_constant_init_type" => parse(value).map(|v| 
+                self.time_constant_init_type = v),
+            "time_constant_replace_rate" => parse(value).map(|v| 
+                self.time_constant_replace_rate = v),
+            "time_constant_mutate_rate" => parse(value).map(|v| 
+                self.time_constant_mutate_rate = v),
+            "time_constant_mutate_power" => parse(value).map(|v| 
+                self.time_constant_mutate_power = v),
+            "time_constant_max_value" => parse(value).map(|v| 
+                self.time_constant_max_value = v),
+            "time_constant_min_value" => parse(value).map(|v| 
+                self.time_constant_min_value = v),
+            "a_init_mean" => parse(value).map(|v| self.a_init_mean = v),
+            "a_init_stdev" => parse(value).map(|v| self.a_init_stdev = v),
+            "a_init_type" => parse(value).map(|v| self.a_init_type = v),
+            "a_replace_rate" => parse(value).map(|v| self.a_replace_rate = v),
+            "a_mutate_rate" => parse(value).map(|v| self.a_mutate_rate = v),
+            "a_mutate_power" => parse(value).map(|v| self.a_mutate_power = v),
+            "a_max_value" => parse(value).map(|v| self.a_max_value = v),
+            "a_min_value" => parse(value).map(|v| self.a_min_value = v),
+            "b_init_mean" => parse(value).map(|v| self.b_init_mean = v),
+            "b_init_stdev" => parse(value).map(|v| self.b_init_stdev = v),
+            "b_init_type" => parse(value).map(|v| self.b_init_type = v),
+            "b_replace_rate" => parse(value).map(|v| self.b_replace_rate = v),
+            "b_mutate_rate" => parse(value).map(|v| self.b_mutate_rate = v),
+            "b_mutate_power" => parse(value).map(|v| self.b_mutate_power = v),
+            "b_max_value" => parse(value).map(|v| self.b_max_value = v),
+            "b_min_value" => parse(value).map(|v| self.b_min_value = v),
+            "c_init_mean" => parse(value).map(|v| self.c_init_mean = v),
+            "c_init_stdev" => parse(value).map(|v| self.c_init_stdev = v),
+            "c_init_type" => parse(value).map(|v| self.c_init_type = v),
+            "c_replace_rate" => parse(value).map(|v| self.c_replace_rate = v),
+            "c_mutate_rate" => parse(value).map(|v| self.c_mutate_rate = v),
+            "c_mutate_power" => parse(value).map(|v| self.c_mutate_power = v),
+            "c_max_value" => parse(value).map(|v| self.c_max_value = v),
+            "c_min_value" => parse(value).map(|v| self.c_min_value = v),
+            "d_init_mean" => parse(value).map(|v| self.d_init_mean = v),
+            "d_init_stdev" => parse(value).map(|v| self.d_init_stdev = v),
+            "d_init_type" => parse(value).map(|v| self.d_init_type = v),
+            "d_replace_rate" => parse(value).map(|v| self.d_replace_rate = v),
+            "d_mutate_rate" => parse(value).map(|v| self.d_mutate_rate = v),
+            "d_mutate_power" => parse(value).map(|v| self.d_mutate_power = v),
+            "d_max_value" => parse(value).map(|v| self.d_max_value = v),
+            "d_min_value" => parse(value).map(|v| self.d_min_value = v),
+            "compatibility_threshold" => parse(value).map(|v| 
+                self.compatibility_threshold = v),
+            "target_num_species" => parse(value).map(|v| 
+                self.target_num_species = v),
+            "compatibility_threshold_modifier" => parse(value).map(|v| 
+                self.compatibility_threshold_modifier = v),
+            "compatibility_threshold_min" => parse(value).map(|v| 
+                self.compatibility_threshold_min = v),
+            "reset_on_extinction" => parse_bool(value).map(|v| 
+                self.reset_on_extinction = v),
+            "species_fitness_func" => parse(value).map(|v| 
+                self.species_fitness_func = v),
+            "max_stagnation" => parse(value).map(|v| self.max_stagnation = v),
+            "species_elitism" => parse(value).map(|v| 
+                self.species_elitism = v),
+            "elitism" => parse(value).map(|v| self.elitism = v),
+            "survival_threshold" => parse(value).map(|v| 
+                self.survival_threshold = v),
+            "min_species_size" => parse(value).map(|v| 
+                self.min_species_size = v),
+            "single_structural_mutation" => parse_bool(value).map(|v| 
+                self.single_structural_mutation = v),
+            "structural_mutation_surer" => parse_bool_or(value, "default")
+                .map(|v| self.structural_mutation_surer = v),
+            "reset_innovations" => parse_bool(value).map(|v| 
+                self.reset_innovations = v),
+            _ => return Err(ConfigError::UnknownKey { 
+                key: key.to_string(), 
+                line: None,
+            }),
+        };
+
+        res.map_err(|message| ConfigError::InvalidValue {
+            key: key.to_string(),
+            line: None,
+            message,
+        })
+    }
+
+    /// Every float parameter together with its key, e.g. to check that a
+    /// config can be written as JSON.
+    pub(crate) fn float_values(&self) -> Vec<(&'static str, f64)> {
+        let mut ret = vec![
+            ("fitness_threshold", self.fitness_threshold),
+            ("compatibility_disjoint_coefficient", 
+                self.compatibility_disjoint_coefficient),
+            ("compatibility_weight_coefficient", 
+                self.compatibility_weight_coefficient),
+            ("activation_mutate_rate", self.activation_mutate_rate),
+            ("aggregation_mutate_rate", self.aggregation_mutate_rate),
+            ("bias_init_mean", self.bias_init_mean),
+            ("bias_init_stdev", self.bias_init_stdev),
+            ("bias_mutate_power", self.bias_mutate_power),
+            ("bias_mutate_rate", self.bias_mutate_rate),
+            ("bias_replace_rate", self.bias_replace_rate),
+            ("bias_max_value", self.bias_max_value),
+            ("bias_min_value", self.bias_min_value),
+            ("conn_add_prob", self.conn_add_prob),
+            ("conn_delete_prob", self.conn_delete_prob),
+            ("node_add_prob", self.node_add_prob),
+            ("node_delete_prob", self.node_delete_prob),
+            ("enabled_mutate_rate", self.enabled_mutate_rate),
+            ("enabled_rate_to_true_add", self.enabled_rate_to_true_add),
+            ("enabled_rate_to_false_add", self.enabled_rate_to_false_add),
+            ("weight_init_mean", self.weight_init_mean),
+            ("weight_init_stdev", self.weight_init_stdev),
+            ("weight_mutate_power", self.weight_mutate_power),
+            ("weight_mutate_rate", self.weight_mutate_rate),
+            ("weight_replace_rate", self.weight_replace_rate),
+            ("weight_max_value", self.weight_max_value),
+            ("weight_min_value", self.weight_min_value),
+            ("response_init_mean", self.response_init_mean),
+            ("response_init_stdev", self.response_init_stdev),
+            ("response_replace_rate", self.response_replace_rate),
+            ("response_mutate_rate", self.response_mutate_rate),
+            ("response_mutate_power", self.response_mutate_power),
+            ("response_max_value", self.response_max_value),
+            ("response_min_value", self.response_min_value),
+            ("time_constant_init_mean", self.time_constant_init_mean),
+            ("time_constant_init_stdev", self.time_constant_init_stdev),
+            ("time_constant_replace_rate", self.time_constant_replace_rate),
+            ("time_constant_mutate_rate", self.time_constant_mutate_rate),
+            ("time_constant_mutate_power", self.time_constant_mutate_power),
+            ("time_constant_max_value", self.time_constant_max_value),
+            ("time_constant_min_value", self.time_constant_min_value),
+            ("a_init_mean", self.a_init_mean),
+            ("a_init_stdev", self.a_init_stdev),
+            ("a_replace_rate", self.a_replace_rate),
+            ("a_mutate_rate", self.a_mutate_rate),
+            ("a_mutate_power", self.a_mutate_power),
+            ("a_max_value", self.a_max_value),
+            ("a_min_value", self.a_min_value),
+            ("b_init_mean", self.b_init_mean),
+            ("b_init_stdev", self.b_init_stdev),
+            ("b_replace_rate", self.b_replace_rate),
+            ("b_mutate_rate", self.b_mutate_rate),
+            ("b_mutate_power", self.b_mutate_power),
+            ("b_max_value", self.b_max_value),
+            ("b_min_value", self.b_min_value),
+            ("c_init_mean", self.c_init_mean),
+            ("c_init_stdev", self.c_init_stdev),
+            ("c_replace_rate", self.c_replace_rate),
+            ("c_mutate_rate", self.c_mutate_rate),
+            ("c_mutate_power", self.c_mutate_power),
+            ("c_max_value", self.c_max_value),
+            ("c_min_value", self.c_min_value),
+            ("d_init_mean", self.d_init_mean),
+            ("d_init_stdev", self.d_init_stdev),
+            ("d_replace_rate", self.d_replace_rate),
+            ("d_mutate_rate", self.d_mutate_rate),
+            ("d_mutate_power", self.d_mutate_power),
+            ("d_max_value", self.d_max_value),
+            ("d_min_value", self.d_min_value),
+            ("compatibility_threshold", self.compatibility_threshold),
+            ("compatibility_threshold_modifier", 
+                self.compatibility_threshold_modifier),
+            ("compatibility_threshold_min", self.compatibility_threshold_min),
+            ("survival_threshold", self.survival_threshold),
+        ];
+        match self.initial_connection {
+            InitialConnection::PartialNoDirect(p) | 
+            InitialConnection::PartialDirect(p) => 
+                ret.push(("initial_connection", p)),
+            _ => {}
+        }
+        ret
+    }
+
+    /// Checks that all values are within their meaningful ranges.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |key: &str, message: &str| Err(ConfigError::InvalidValue {
+            key: key.to_string(),
+            line: None,
+            message: message.to_string(),
+        });
+
+        let probabilities = [
+            ("activation_mutate_rate", self.activation_mutate_rate),
+            ("aggregation_mutate_rate", self.aggregation_mutate_rate),
+            ("bias_mutate_rate", self.bias_mutate_rate),
+            ("bias_replace_rate", self.bias_replace_rate),
+            ("conn_add_prob", self.conn_add_prob),
+            ("conn_delete_prob", self.conn_delete_prob),
+            ("node_add_prob", self.node_add_prob),
+            ("node_delete_prob", self.node_delete_prob),
+            ("enabled_mutate_rate", self.enabled_mutate_rate),
+            ("weight_mutate_rate", self.weight_mutate_rate),
+            ("weight_replace_rate", self.weight_replace_rate),
+            ("response_mutate_rate", self.response_mutate_rate),
+            ("response_replace_rate", self.response_replace_rate),
+            ("time_constant_mutate_rate", self.time_constant_mutate_rate),
+            ("time_constant_replace_rate", self.time_constant_replace_rate),
+            ("a_mutate_rate", self.a_mutate_rate),
+            ("a_replace_rate", self.a_replace_rate),
+            ("b_mutate_rate", self.b_mutate_rate),
+            ("b_replace_rate", self.b_replace_rate),
+            ("c_mutate_rate", self.c_mutate_rate),
+            ("c_replace_rate", self.c_replace_rate),
+            ("d_mutate_rate", self.d_mutate_rate),
+            ("d_replace_rate", self.d_replace_rate),
+            ("survival_threshold", self.survival_threshold),
+        ];
+        for (key, p) in probabilities {
+            if !(0.0..=1.0).contains(&p) {
+                return invalid(key, "must be between 0 and 1");
+            }
+        }
+
+        let powers = [
+            ("bias_init_stdev", self.bias_init_stdev),
+            ("bias_mutate_power", self.bias_mutate_power),
+            ("weight_init_stdev", self.weight_init_stdev),
+            ("weight_mutate_power", self.weight_mutate_power),
+            ("response_init_stdev", self.response_init_stdev),
+            ("response_mutate_power", self.response_mutate_power),
+            ("time_constant_init_stdev", self.time_constant_init_stdev),
+            ("time_constant_mutate_power", self.time_constant_mutate_power),
+            ("a_init_stdev", self.a_init_stdev),
+            ("a_mutate_power", self.a_mutate_power),
+            ("b_init_stdev", self.b_init_stdev),
+            ("b_mutate_power", self.b_mutate_power),
+            ("c_init_stdev", self.c_init_stdev),
+            ("c_mutate_power", self.c_mutate_power),
+            ("d_init_stdev", self.d_init_stdev),
+            ("d_mutate_power", self.d_mutate_power),
+            ("compatibility_threshold", self.compatibility_threshold),
+            ("compatibility_threshold_modifier", 
+                self.compatibility_threshold_modifier),
+            ("compatibility_threshold_min", self.compatibility_threshold_min),
+            ("compatibility_disjoint_coefficient", 
+                self.compatibility_disjoint_coefficient),
+            ("compatibility_weight_coefficient", 
+                self.compatibility_weight_coefficient),
+        ];
+        for (key, p) in powers {
+            if !(p >= 0.0 && p.is_finite()) {
+                return invalid(key, "must be a finite, non-negative number");
+            }
+        }
+
+        let ranges = [
+            ("bias_min_value", self.bias_min_value, self.bias_max_value),
+            ("weight_min_value", self.weight_min_value, 
+                self.weight_max_value),
+            ("response_min_value", self.response_min_value, 
+                self.response_max_value),
+            ("time_constant_min_value", self.time_constant_min_value,
+                self.time_constant_max_value),
+            ("a_min_value", self.a_min_value, self.a_max_value),
+            ("b_min_value", self.b_min_value, self.b_max_value),
+            ("c_min_value", self.c_min_value, self.c_max_value),
+            ("d_min_value", self.d_min_value, self.d_max_value),
+        ];
+        for (key, min, max) in ranges {
+            if min > max || min.is_nan() || max.is_nan() {
+                return invalid(key, "must not be larger than the maximum");
+            }
+        }
+
+        match self.initial_connection {
+            InitialConnection::PartialNoDirect(p) | 
+            InitialConnection::PartialDirect(p) if !(0.0..=1.0).contains(&p) => 
+                return invalid("initial_connection", 
+                    "connection probability must be between 0 and 1"),
+            _ => {}
+        }
+
+        if self.time_constant_min_value <= 0.0 {
+            return invalid("time_constant_min_value", "must be positive");
+        }
+
+        if self.activation_options.is_empty() {
+            return invalid("activation_options", "must not be empty");
+        }
+        if self.aggregation_options.is_empty() {
+            return invalid("aggregation_options", "must not be empty");
+        }
+
+        if self.pop_size == 0 {
+            return invalid("pop_size", "must be at least 1");
+        }
+        if self.num_inputs == 0 {
+            return invalid("num_inputs", "must be at least 1");
+        }
+        if self.num_outputs == 0 {
+            return invalid("num_outputs", "must be at least 1");
+        }
+        if self.elitism > self.pop_size {
+            return invalid("elitism", "must not exceed pop_size");
+        }
+        if self.min_species_size.max(self.elitism) == 0 {
+            return invalid("min_species_size", 
+                "must be at least 1 if elitism is 0");
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{genome::Genome, innovation::InnovationTracker};
+
+    const INI: &str = "
+[NEAT]
+fitness_criterion     = mean
+fitness_threshold     = 3.5
+pop_size              = 50
+reset_on_extinction   = False
+no_fitness_termination = true
+
+[DefaultGenome]
+num_inputs            = 2
+num_outputs           = 1
+num_hidden            = 1
+initial_connection    = partial_direct 0.5
+activation_default    = tanh
+activation_options    = tanh
+    gauss
+aggregation_default   = random
+aggregation_options   = sum product
+bias_init_mean        = 0.5
+enabled_default       = random
+structural_mutation_surer = true
+
+[DefaultSpeciesSet]
+compatibility_threshold = 3.0
+
+[DefaultStagnation]
+species_fitness_func = median
+max_stagnation       = 20
+
+[DefaultReproduction]
+elitism            = 2
+survival_threshold = 0.2
+";
+
+    #[test]
+    fn loads_neat_python_ini() {
+        let config = NeatConfig::from_ini_str(INI).unwrap();
+        assert_eq!(config.fitness_criterion, FitnessFunction::Mean);
+        assert_eq!(config.fitness_threshold, 3.5);
+        assert!(config.no_fitness_termination);
+        assert_eq!(config.pop_size, 50);
+        assert!(!config.reset_on_extinction);
+        assert_eq!(config.num_hidden, 1);
+        assert_eq!(config.initial_connection, 
+            InitialConnection::PartialDirect(0.5));
+        assert_eq!(config.activation_default, 
+            Some(ActivationFunction::Tanh));
+        assert_eq!(config.activation_options, 
+            [ActivationFunction::Tanh, ActivationFunction::Gauss]);
+        assert_eq!(config.aggregation_default, None);
+        assert_eq!(config.aggregation_options, 
+            [AggregationFunction::Sum, AggregationFunction::Product]);
+        assert_eq!(config.bias_init_mean, 0.5);
+        assert_eq!(config.enabled_default, None);
+        assert_eq!(config.structural_mutation_surer, Some(true));
+        assert_eq!(config.compatibility_threshold, 3.0);
+        assert_eq!(config.species_fitness_func, FitnessFunction::Median);
+        assert_eq!(config.max_stagnation, 20);
+    }
+
+    #[test]
+    fn genomes_only_use_configured_functions() {
+        let config = NeatConfig {
+            activation_mutate_rate: 1.0,
+            aggregation_mutate_rate: 1.0,
+            ..NeatConfig::from_ini_str(INI).unwrap()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&config);
+
+        let mut genome = Genome::new(0, &config, &mut rng);
+        assert!(genome.nodes().values()
+            .all(|n| n.get_activation() == ActivationFunction::Tanh));
+        for _ in 0..50 {
+            genome.mutate(&config, &mut innovations, &mut rng);
+            for node in genome.nodes().values() {
+                assert!(config.activation_options
+                    .contains(&node.get_activation()));
+                assert!(config.aggregation_options
+                    .contains(&node.get_aggregation()));
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_values_are_rejected_with_their_line() {
+        let text = "[DefaultGenome]\nnum_inputs = 2\n\
+            aggregation_options = sum median\n";
+        match NeatConfig::from_ini_str(text) {
+            Err(ConfigError::InvalidValue { key, line, .. }) => {
+                assert_eq!(key, "aggregation_options");
+                assert_eq!(line, Some(3));
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        match NeatConfig::from_ini_str("[NEAT]\n\nno_such_key = 1\n") {
+            Err(ConfigError::UnknownKey { key, line }) => {
+                assert_eq!(key, "no_such_key");
+                assert_eq!(line, Some(3));
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        match NeatConfig::from_ini_str("[DefaultGenome]\nactivation_options =") 
+        {
+            Err(ConfigError::InvalidValue { key, line, .. }) => {
+                assert_eq!(key, "activation_options");
+                assert_eq!(line, Some(2));
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn validation_names_the_offending_key() {
+        let text = "[DefaultGenome]\nbias_min_value = 1\nbias_max_value = 0\n";
+        match NeatConfig::from_ini_str(text) {
+            Err(ConfigError::InvalidValue { key, line, .. }) => {
+                assert_eq!(key, "bias_min_value");
+                assert_eq!(line, Some(2));
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        let config = NeatConfig { 
+            survival_threshold: 1.5, 
+            ..NeatConfig::default() 
+        };
+        assert!(config.validate().is_err());
+
+        let config = NeatConfig { 
+            min_species_size: 0, 
+            elitism: 0, 
+            ..NeatConfig::default() 
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn loads_toml_and_json() {
+        let toml = r#"
+            [NEAT]
+            pop_size = 20
+            [DefaultGenome]
+            activation_default = "random"
+            activation_options = "sigmoid relu"
+            aggregation_options = ["max", "min"]
+            structural_mutation_surer = "default"
+        "#;
+        let config = NeatConfig::from_toml_str(toml).unwrap();
+        assert_eq!(config.pop_size, 20);
+        assert_eq!(config.activation_default, None);
+        assert_eq!(config.activation_options, 
+            [ActivationFunction::Sigmoid, ActivationFunction::Relu]);
+        assert_eq!(config.aggregation_options, 
+            [AggregationFunction::Max, AggregationFunction::Min]);
+        assert_eq!(config.structural_mutation_surer, None);
+
+        assert!(matches!(
+            NeatConfig::from_json_str(r#"{"activation_default": "nope"}"#),
+            Err(ConfigError::InvalidValue { .. })));
+        assert!(matches!(NeatConfig::from_json_str(r#"{"nope": 1}"#),
+            Err(ConfigError::UnknownKey { .. })));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let config = NeatConfig {
+            activation_default: None,
+            enabled_default: None,
+            initial_connection: InitialConnection::PartialNoDirect(0.3),
+            seed: Some(7),
+            ..NeatConfig::from_ini_str(INI).unwrap()
+        };
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(NeatConfig::from_json_str(&json).unwrap(), config);
+    }
+}
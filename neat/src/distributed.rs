@@ -0,0 +1,529 @@
+//! Evaluates the fitness of a population on worker processes, possibly on
+//! other hosts, connected over TCP or Unix sockets.
+//!
+//! Coordinator and workers exchange line-delimited JSON. The coordinator
+//! sends one genome at a time to every worker and hands out the next one as
+//! soon as its result is back, so faster workers evaluate more genomes:
+//!
+//! ```text
+//! -> {"evaluate":{"job":7,"genome":{...}}}
+//! <- {"fitness":{"job":7,"fitness":3.5}}
+//! -> "shutdown"
+//! ```
+//!
+//! A worker may answer `{"failed":{"job":7}}` if it could not evaluate the
+//! genome. A worker that disconnects, sends garbage or does not answer
+//! within the timeout is dropped and its genome is handed to another one.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+#[cfg(unix)]
+use std::{
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+};
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::NeatConfig,
+    evaluation::{EvaluationFailure, OnFailure},
+    genome::Genome,
+};
+
+/// Number of workers a genome is tried on before it is given the failure
+/// fitness.
+pub const MAX_ATTEMPTS: usize = 3;
+
+// externally tagged, as internally tagged enums cannot deserialize the
+// integer keyed maps of a genome
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Request {
+    Evaluate { job: u64, genome: Genome },
+    Shutdown,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    Fitness { job: u64, fitness: f64 },
+    Failed { job: u64 },
+}
+
+/// A connection to a worker or coordinator.
+trait Stream: io::Read + Write + Send + Sized + 'static {
+    fn try_clone(&self) -> io::Result<Self>;
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl Stream for TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(unix)]
+impl Stream for UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        UnixStream::try_clone(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+fn send<W: Write, T: Serialize>(writer: &mut W, msg: &T) -> io::Result<()> {
+    let mut line = serde_json::to_vec(msg)?;
+    line.push(b'\n');
+    writer.write_all(&line)?;
+    writer.flush()
+}
+
+fn receive<R: BufRead, T>(reader: &mut R) -> io::Result<Option<T>>
+        where T: for<'de> Deserialize<'de> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+struct Job {
+    id:       u64,
+    attempts: usize,
+    genome:   Genome,
+}
+
+enum Event {
+    // `None` if the worker failed to evaluate the genome
+    Done(u64, Option<f64>),
+    // the worker died before finishing the job
+    Requeue(Job),
+}
+
+/// State shared with the threads serving the workers.
+#[derive(Clone)]
+struct Shared {
+    jobs:        Arc<Mutex<Receiver<Job>>>,
+    events:      Sender<Event>,
+    timeout:     Option<Duration>,
+    num_workers: Arc<AtomicUsize>,
+}
+
+impl Shared {
+    fn spawn_handler<S: Stream>(&self, stream: S) {
+        let shared = self.clone();
+        shared.num_workers.fetch_add(1, Ordering::SeqCst);
+        thread::spawn(move || {
+            shared.serve(stream);
+            shared.num_workers.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+
+    // feeds jobs to one worker until it dies or the coordinator is dropped
+    fn serve<S: Stream>(&self, stream: S) {
+        let Ok(reader) = stream.try_clone() else { return };
+        if stream.set_read_timeout(self.timeout).is_err() {
+            return;
+        }
+        let mut reader = BufReader::new(reader);
+        let mut writer = stream;
+
+        loop {
+            let job = self.jobs.lock().unwrap().recv();
+            let Ok(job) = job else {
+                let _ = send(&mut writer, &Request::Shutdown);
+                return;
+            };
+
+            let request = Request::Evaluate { 
+                job: job.id, 
+                genome: job.genome.clone(),
+            };
+            let response = send(&mut writer, &request)
+                .and_then(|_| receive(&mut reader));
+            let event = match response {
+                Ok(Some(Response::Fitness { job: id, fitness })) 
+                    if id == job.id => Event::Done(id, Some(fitness)),
+                Ok(Some(Response::Failed { job: id })) 
+                    if id == job.id => Event::Done(id, None),
+                _ => {
+                    let _ = self.events.send(Event::Requeue(job));
+                    return;
+                }
+            };
+            if self.events.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Hands the genomes of a population out to the workers connected to it.
+///
+/// Workers may connect and disconnect at any time; [`Coordinator::evaluate`]
+/// waits until every genome got a fitness, which requires at least one
+/// worker to be connected. A genome whose worker died is handed to another
+/// worker. Genomes a worker failed to evaluate, or which [`MAX_ATTEMPTS`]
+/// workers died on, are dealt with as set by 
+/// [`Coordinator::on_failure_mut`].
+///
+/// With a timeout, a worker that takes longer than that to answer is 
+/// considered dead. Without, a worker on a host that went away without
+/// closing the connection may stall the run.
+///
+/// Dropping the coordinator tells connected workers to shut down.
+pub struct Coordinator {
+    jobs:       Sender<Job>,
+    events:     Mutex<Receiver<Event>>,
+    shared:     Shared,
+    next_job:   AtomicU64,
+    on_failure: OnFailure,
+    local_addr: Option<SocketAddr>,
+}
+
+impl Coordinator {
+    fn new(timeout: Option<Duration>) -> Self {
+        let (jobs, job_rx) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+        Self {
+            jobs,
+            events: Mutex::new(events),
+            shared: Shared {
+                jobs: Arc::new(Mutex::new(job_rx)),
+                events: event_tx,
+                timeout,
+                num_workers: Arc::new(AtomicUsize::new(0)),
+            },
+            next_job: AtomicU64::new(0),
+            on_failure: OnFailure::default(),
+            local_addr: None,
+        }
+    }
+
+    /// Listens for workers on a TCP address.
+    pub fn bind_tcp<A: ToSocketAddrs>(addr: A, timeout: Option<Duration>) 
+            -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let mut coordinator = Self::new(timeout);
+        coordinator.local_addr = Some(listener.local_addr()?);
+
+        let shared = coordinator.shared.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = stream.set_nodelay(true);
+                shared.spawn_handler(stream);
+            }
+        });
+        Ok(coordinator)
+    }
+
+    /// Listens for workers on a Unix socket at `path`, which must not exist
+    /// yet.
+    #[cfg(unix)]
+    pub fn bind_unix<P: AsRef<Path>>(path: P, timeout: Option<Duration>) 
+            -> io::Result<Self> {
+        let listener = UnixListener::bind(path)?;
+        let coordinator = Self::new(timeout);
+
+        let shared = coordinator.shared.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                shared.spawn_handler(stream);
+            }
+        });
+        Ok(coordinator)
+    }
+
+    /// The address listened on when bound to TCP, e.g. to find out the port
+    /// picked when binding to port 0.
+    #[inline]
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Number of workers currently connected.
+    #[inline]
+    pub fn num_workers(&self) -> usize {
+        self.shared.num_workers.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub fn on_failure(&self) -> &OnFailure {
+        &self.on_failure
+    }
+
+    /// Failure fitness and handler for genomes no worker managed to 
+    /// evaluate.
+    #[inline]
+    pub fn on_failure_mut(&mut self) -> &mut OnFailure {
+        &mut self.on_failure
+    }
+
+    /// Sets the fitness of every genome, blocking until each one was 
+    /// evaluated or given up on. Like [`ParallelEvaluator::evaluate`], it
+    /// can be called from the fitness function passed to 
+    /// [`Population::run`].
+    ///
+    /// [`ParallelEvaluator::evaluate`]: 
+    ///     crate::parallel::ParallelEvaluator::evaluate
+    /// [`Population::run`]: crate::population::Population::run
+    pub fn evaluate(&self, genomes: &mut [&mut Genome], _config: &NeatConfig) {
+        let events = self.events.lock().unwrap();
+
+        // genome index of every job not done yet
+        let mut pending: HashMap<u64, usize> = HashMap::new();
+        for (i, g) in genomes.iter().enumerate() {
+            let id = self.next_job.fetch_add(1, Ordering::SeqCst);
+            pending.insert(id, i);
+            let job = Job { id, attempts: 0, genome: (**g).clone() };
+            self.jobs.send(job).unwrap();
+        }
+
+        while !pending.is_empty() {
+            // `self.shared` keeps an event sender alive
+            match events.recv().unwrap() {
+                Event::Done(id, fitness) => {
+                    let Some(i) = pending.remove(&id) else { continue };
+                    match fitness {
+                        Some(fitness) => genomes[i].set_fitness(fitness),
+                        None => self.on_failure.fail(genomes[i], 
+                            EvaluationFailure::Failed),
+                    }
+                }
+                Event::Requeue(mut job) => {
+                    let Some(&i) = pending.get(&job.id) else { continue };
+                    job.attempts += 1;
+                    if job.attempts < MAX_ATTEMPTS {
+                        self.jobs.send(job).unwrap();
+                        continue;
+                    }
+                    self.on_failure.fail(genomes[i], 
+                        EvaluationFailure::Abandoned);
+                    pending.remove(&job.id);
+                }
+            }
+        }
+    }
+}
+
+fn run_worker<S, F>(stream: S, eval_fn: F) -> io::Result<()>
+        where S: Stream, F: Fn(&Genome) -> f64 {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    while let Some(request) = receive(&mut reader)? {
+        let Request::Evaluate { job, genome } = request else { break };
+
+        let fitness = panic::catch_unwind(
+            AssertUnwindSafe(|| eval_fn(&genome)));
+        // non-finite numbers have no JSON representation
+        let response = match fitness {
+            Ok(fitness) if fitness.is_finite() => 
+                Response::Fitness { job, fitness },
+            _ => Response::Failed { job },
+        };
+        send(&mut writer, &response)?;
+    }
+    Ok(())
+}
+
+/// Connects to a coordinator over TCP and evaluates the genomes it sends 
+/// until it shuts down or closes the connection.
+///
+/// Evaluations that panic or return a non-finite fitness are reported to
+/// the coordinator as failed.
+pub fn run_tcp_worker<A, F>(addr: A, eval_fn: F) -> io::Result<()>
+        where A: ToSocketAddrs, F: Fn(&Genome) -> f64 {
+    let stream = TcpStream::connect(addr)?;
+    stream.set_nodelay(true)?;
+    run_worker(stream, eval_fn)
+}
+
+/// Like [`run_tcp_worker`], over the Unix socket at `path`.
+#[cfg(unix)]
+pub fn run_unix_worker<P, F>(path: P, eval_fn: F) -> io::Result<()>
+        where P: AsRef<Path>, F: Fn(&Genome) -> f64 {
+    run_worker(UnixStream::connect(path)?, eval_fn)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::evaluation::testing::{genomes, record_failures};
+
+    fn evaluate(coordinator: &Coordinator, genomes: &mut [Genome]) {
+        let mut refs: Vec<&mut Genome> = genomes.iter_mut().collect();
+        coordinator.evaluate(&mut refs, &NeatConfig::default());
+    }
+
+    fn wait_for_workers(coordinator: &Coordinator, n: usize) {
+        let start = Instant::now();
+        while coordinator.num_workers() != n {
+            assert!(start.elapsed() < Duration::from_secs(5), 
+                "{} workers connected", coordinator.num_workers());
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// Connects to the coordinator, takes a job and disconnects without
+    /// answering.
+    fn die_on_first_job(addr: SocketAddr) {
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(stream);
+        let request: Option<Request> = receive(&mut reader).unwrap();
+        assert!(matches!(request, Some(Request::Evaluate { .. })));
+    }
+
+    #[test]
+    fn tcp_workers_evaluate_every_genome() {
+        let coordinator = Coordinator::bind_tcp("127.0.0.1:0", None).unwrap();
+        let addr = coordinator.local_addr().unwrap();
+        let workers: Vec<_> = (0..2)
+            .map(|_| thread::spawn(move || run_tcp_worker(addr, 
+                |g: &Genome| g.get_id() as f64 + 0.5)))
+            .collect();
+        wait_for_workers(&coordinator, 2);
+
+        let mut genomes = genomes(10);
+        evaluate(&coordinator, &mut genomes);
+        for g in genomes.iter() {
+            assert_eq!(g.get_fitness(), g.get_id() as f64 + 0.5);
+        }
+
+        drop(coordinator);
+        for worker in workers {
+            worker.join().unwrap().unwrap();
+        }
+    }
+
+    #[test]
+    fn failed_evaluations_get_the_failure_fitness() {
+        let mut coordinator = Coordinator::bind_tcp("127.0.0.1:0", None)
+            .unwrap();
+        coordinator.on_failure_mut().set_fitness(-1.0);
+        let failures = record_failures(coordinator.on_failure_mut());
+        let addr = coordinator.local_addr().unwrap();
+        thread::spawn(move || run_tcp_worker(addr, |g: &Genome| {
+            if g.get_id() == 1 { f64::NAN } else { 1.0 }
+        }));
+        wait_for_workers(&coordinator, 1);
+
+        let mut genomes = genomes(3);
+        evaluate(&coordinator, &mut genomes);
+        let fitnesses: Vec<f64> = genomes.iter()
+            .map(|g| g.get_fitness())
+            .collect();
+        assert_eq!(fitnesses, [1.0, -1.0, 1.0]);
+        assert_eq!(*failures.lock().unwrap(), 
+            [(1, EvaluationFailure::Failed)]);
+    }
+
+    #[test]
+    fn jobs_of_dead_workers_are_requeued() {
+        let mut coordinator = Coordinator::bind_tcp("127.0.0.1:0", None)
+            .unwrap();
+        let failures = record_failures(coordinator.on_failure_mut());
+        let addr = coordinator.local_addr().unwrap();
+        let dying = thread::spawn(move || die_on_first_job(addr));
+        wait_for_workers(&coordinator, 1);
+        thread::spawn(move || {
+            dying.join().unwrap();
+            run_tcp_worker(addr, |_: &Genome| 2.0)
+        });
+
+        let mut genomes = genomes(4);
+        evaluate(&coordinator, &mut genomes);
+        assert!(genomes.iter().all(|g| g.get_fitness() == 2.0));
+        assert!(failures.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn genomes_are_abandoned_after_max_attempts() {
+        let mut coordinator = Coordinator::bind_tcp("127.0.0.1:0", None)
+            .unwrap();
+        coordinator.on_failure_mut().set_fitness(-1.0);
+        let failures = record_failures(coordinator.on_failure_mut());
+        let addr = coordinator.local_addr().unwrap();
+        thread::spawn(move || {
+            for _ in 0..MAX_ATTEMPTS {
+                die_on_first_job(addr);
+            }
+        });
+
+        let mut genomes = genomes(1);
+        evaluate(&coordinator, &mut genomes);
+        assert_eq!(genomes[0].get_fitness(), -1.0);
+        assert_eq!(*failures.lock().unwrap(), 
+            [(0, EvaluationFailure::Abandoned)]);
+    }
+
+    #[test]
+    fn unresponsive_workers_time_out() {
+        let coordinator = Coordinator::bind_tcp("127.0.0.1:0", 
+            Some(Duration::from_millis(100))).unwrap();
+        let addr = coordinator.local_addr().unwrap();
+        let (received, has_received) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+        // takes a job and then never answers
+        thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            let mut reader = BufReader::new(stream);
+            let _: Option<Request> = receive(&mut reader).unwrap();
+            received.send(()).unwrap();
+            let _ = released.recv();
+        });
+        thread::spawn(move || {
+            has_received.recv().unwrap();
+            run_tcp_worker(addr, |_: &Genome| 3.0)
+        });
+
+        let mut genomes = genomes(3);
+        evaluate(&coordinator, &mut genomes);
+        assert!(genomes.iter().all(|g| g.get_fitness() == 3.0));
+        drop(release);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_workers_evaluate_every_genome() {
+        let path = std::env::temp_dir()
+            .join(format!("neat-distributed-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let coordinator = Coordinator::bind_unix(&path, None).unwrap();
+        let worker_path = path.clone();
+        let worker = thread::spawn(move || run_unix_worker(worker_path, 
+            |g: &Genome| g.get_id() as f64));
+        wait_for_workers(&coordinator, 1);
+
+        let mut genomes = genomes(5);
+        evaluate(&coordinator, &mut genomes);
+        for g in genomes.iter() {
+            assert_eq!(g.get_fitness(), g.get_id() as f64);
+        }
+
+        drop(coordinator);
+        worker.join().unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,178 @@
+//! Graphviz DOT export of genomes.
+
+use std::{fmt::Write as _, fs, io, path::Path};
+
+use hashbrown::{HashMap, HashSet};
+
+use crate::{genome::Genome, graphs};
+
+/// Options for [`genome_to_dot`].
+#[derive(Debug, Clone)]
+pub struct DotOptions {
+    /// Names to show instead of node keys, e.g. for inputs and outputs.
+    /// They are shown verbatim, quotes and backslashes are escaped.
+    pub node_names:    HashMap<usize, String>,
+    /// Whether disabled connections are drawn, dashed.
+    pub show_disabled: bool,
+    /// Whether nodes that cannot reach an output through enabled 
+    /// connections are hidden, inputs included, which leaves what a compiled
+    /// network would evaluate.
+    pub prune_unused:  bool,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        Self {
+            node_names: HashMap::new(),
+            show_disabled: true,
+            prune_unused: false,
+        }
+    }
+}
+
+/// Escapes `name` for use inside a quoted DOT string.
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Renders `genome` as a directed graph. Inputs are grey boxes, outputs blue
+/// and hidden nodes white; hidden and output nodes are labelled with their
+/// activation, aggregation, bias and response. Edges are green for positive
+/// and red for negative weights, and their width grows with the magnitude of
+/// the weight.
+pub fn genome_to_dot(genome: &Genome, options: &DotOptions) -> String {
+    let mut keys: Vec<usize> = genome.nodes().keys().copied().collect();
+    keys.sort_unstable();
+    let mut connections: Vec<_> = genome.connections().values()
+        .filter(|c| options.show_disabled || c.is_enabled())
+        .collect();
+    connections.sort_by_key(|c| c.get_id());
+
+    if options.prune_unused {
+        let enabled: Vec<(usize, usize)> = genome.connections().values()
+            .filter(|c| c.is_enabled())
+            .map(|c| (c.get_src(), c.get_dst()))
+            .collect();
+        let required = graphs::required_for_output(genome.input_keys(),
+            genome.output_keys(), &enabled);
+        let read: HashSet<usize> = enabled.iter()
+            .filter(|(_, dst)| required.contains(dst))
+            .map(|&(src, _)| src)
+            .collect();
+        let used = |k: &usize| required.contains(k) || read.contains(k);
+        keys.retain(used);
+        connections.retain(|c| used(&c.get_src()) && used(&c.get_dst()));
+    }
+
+    let name = |k: usize| options.node_names.get(&k)
+        .map_or_else(|| k.to_string(), |n| escape(n));
+
+    let mut ret = String::new();
+    writeln!(ret, "digraph genome_{} {{", genome.get_id()).unwrap();
+    writeln!(ret, "    node [fontsize=9, height=0.2, width=0.2];").unwrap();
+    for k in keys {
+        if genome.input_keys().contains(&k) {
+            writeln!(ret, "    n{} [label=\"{}\", shape=box, style=filled, \
+                fillcolor=lightgray];", k, name(k)).unwrap();
+            continue;
+        }
+
+        let node = &genome.nodes()[&k];
+        let fill = if genome.output_keys().contains(&k) {
+            "lightblue"
+        } else {
+            "white"
+        };
+        writeln!(ret, "    n{} [label=\"{}\\n{:?}/{:?}\\nbias={:.3} \
+            response={:.3}\", style=filled, fillcolor={}];", k, name(k),
+            node.get_activation(), node.get_aggregation(), node.get_bias(),
+            node.get_response(), fill).unwrap();
+    }
+
+    for conn in connections {
+        let weight = conn.get_weight();
+        writeln!(ret, "    n{} -> n{} [label=\"{:.3}\", style={}, color={}, \
+            penwidth={:.3}];", conn.get_src(), conn.get_dst(), weight,
+            if conn.is_enabled() { "solid" } else { "dashed" },
+            if weight > 0.0 { "green" } else { "red" },
+            0.1 + weight.abs() / 5.0).unwrap();
+    }
+    ret.push_str("}\n");
+    ret
+}
+
+/// Writes [`genome_to_dot`] to `path`.
+pub fn write_genome_dot<P: AsRef<Path>>(genome: &Genome, options: &DotOptions,
+        path: P) -> io::Result<()> {
+    fs::write(path, genome_to_dot(genome, options))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        config::{InitialConnection, NeatConfig},
+        innovation::InnovationTracker,
+    };
+
+    #[test]
+    fn node_names_are_escaped() {
+        let config = NeatConfig {
+            num_inputs: 2,
+            num_outputs: 1,
+            ..NeatConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let genome = Genome::new(0, &config, &mut rng);
+        let options = DotOptions {
+            node_names: [
+                (genome.input_keys()[0], "say \"hi\"".to_string()),
+                (genome.input_keys()[1], "C:\\dir".to_string()),
+            ].into_iter().collect(),
+            ..DotOptions::default()
+        };
+
+        let dot = genome_to_dot(&genome, &options);
+        assert!(dot.contains(r#"[label="say \"hi\"", shape=box"#), "{}", dot);
+        assert!(dot.contains(r#"[label="C:\\dir", shape=box"#), "{}", dot);
+    }
+
+    #[test]
+    fn nodes_that_reach_no_output_are_pruned() {
+        let config = NeatConfig {
+            num_inputs: 3,
+            num_outputs: 1,
+            num_hidden: 1,
+            initial_connection: InitialConnection::Unconnected,
+            ..NeatConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&config);
+        let mut genome = Genome::new(0, &config, &mut rng);
+        // input 0 feeds the output 3, input 1 only the dead end 4, input 2
+        // nothing
+        genome.add_connection(&mut innovations, 0, 3, 1.0, true);
+        genome.add_connection(&mut innovations, 1, 4, 1.0, true);
+
+        let options = DotOptions {
+            prune_unused: true,
+            ..DotOptions::default()
+        };
+        let dot = genome_to_dot(&genome, &options);
+        let nodes: Vec<&str> = dot.lines()
+            .filter(|l| l.contains("[label=") && !l.contains("->"))
+            .map(|l| l.trim().split(' ').next().unwrap())
+            .collect();
+        assert_eq!(nodes, ["n0", "n3"]);
+        assert!(dot.contains("n0 -> n3"), "{}", dot);
+        assert!(!dot.contains("n1 -> n4"), "{}", dot);
+
+        let dot = genome_to_dot(&genome, &DotOptions::default());
+        assert!(dot.contains("n1 -> n4"), "{}", dot);
+        assert!(dot.contains("    n2 [label="), "{}", dot);
+    }
+}
@@ -0,0 +1,87 @@
+//! Handling of failed evaluations, shared by the evaluators in
+//! [`parallel`](crate::parallel) and [`distributed`](crate::distributed).
+
+use crate::genome::Genome;
+
+/// Why a genome was given the failure fitness.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvaluationFailure {
+    /// The fitness function panicked on a local thread.
+    Panicked,
+    /// The fitness function ran longer than the timeout of a local thread.
+    TimedOut,
+    /// A remote worker reported that it could not evaluate the genome.
+    Failed,
+    /// [`MAX_ATTEMPTS`](crate::distributed::MAX_ATTEMPTS) remote workers
+    /// died while evaluating the genome.
+    Abandoned,
+}
+
+type FailureHandler = Box<dyn Fn(&Genome, EvaluationFailure) + Send + Sync>;
+
+/// What an evaluator does with a genome it could not evaluate: it is given
+/// the failure fitness, 0 unless set otherwise, and handed to the failure
+/// handler, if one is set. The run goes on either way.
+#[derive(Default)]
+pub struct OnFailure {
+    fitness: f64,
+    handler: Option<FailureHandler>,
+}
+
+impl OnFailure {
+    #[inline]
+    pub fn get_fitness(&self) -> f64 {
+        self.fitness
+    }
+
+    #[inline]
+    pub fn set_fitness(&mut self, fitness: f64) {
+        self.fitness = fitness;
+    }
+
+    /// Calls `handler` with every genome that is given the failure fitness,
+    /// on the thread that called the evaluator.
+    pub fn set_handler<H>(&mut self, handler: H)
+            where H: Fn(&Genome, EvaluationFailure) + Send + Sync + 'static {
+        self.handler = Some(Box::new(handler));
+    }
+
+    pub(crate) fn fail(&self, genome: &mut Genome,
+            failure: EvaluationFailure) {
+        genome.set_fitness(self.fitness);
+        if let Some(handler) = self.handler.as_ref() {
+            handler(genome, failure);
+        }
+    }
+}
+
+
+/// Fixtures for the tests of the evaluators.
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::sync::{Arc, Mutex};
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::config::NeatConfig;
+
+    pub(crate) fn genomes(n: usize) -> Vec<Genome> {
+        let config = NeatConfig::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        (0..n).map(|id| Genome::new(id, &config, &mut rng)).collect()
+    }
+
+    pub(crate) type Failures = Arc<Mutex<Vec<(usize, EvaluationFailure)>>>;
+
+    /// Installs a handler that records the id of every failed genome.
+    pub(crate) fn record_failures(on_failure: &mut OnFailure) -> Failures {
+        let failures = Failures::default();
+        let recorded = Arc::clone(&failures);
+        on_failure.set_handler(move |genome, failure| {
+            recorded.lock().unwrap().push((genome.get_id(), failure));
+        });
+        failures
+    }
+}
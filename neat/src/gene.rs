@@ -1,17 +1,118 @@
 use hashbrown::HashSet;
-use rand::{random, thread_rng, Rng};
+use rand::{seq::SliceRandom, Rng};
 use rand_distr::{Normal, Distribution};
+use serde::{Deserialize, Serialize};
 
-use crate::config;
+use crate::config::{InitType, NeatConfig};
 use crate::activation::ActivationFunction;
 use crate::aggregation::AggregationFunction;
 
-#[derive(Debug, Clone)]
-pub(crate) struct NodeGene {
+/// Settings of one floating point attribute of a gene, gathered from the
+/// `<name>_*` keys of a [`NeatConfig`].
+pub(crate) struct FloatAttribute {
+    init_mean:    f64,
+    init_stdev:   f64,
+    init_type:    InitType,
+    mutate_rate:  f64,
+    mutate_power: f64,
+    replace_rate: f64,
+    min:          f64,
+    max:          f64,
+}
+
+macro_rules! float_attribute {
+    ($name:ident, $init_mean:ident, $init_stdev:ident, $init_type:ident,
+            $mutate_rate:ident, $mutate_power:ident, $replace_rate:ident,
+            $min:ident, $max:ident) => {
+        pub(crate) fn $name(config: &NeatConfig) -> Self {
+            Self {
+                init_mean: config.$init_mean,
+                init_stdev: config.$init_stdev,
+                init_type: config.$init_type,
+                mutate_rate: config.$mutate_rate,
+                mutate_power: config.$mutate_power,
+                replace_rate: config.$replace_rate,
+                min: config.$min,
+                max: config.$max,
+            }
+        }
+    };
+}
+
+impl FloatAttribute {
+    float_attribute!(bias, bias_init_mean, bias_init_stdev, bias_init_type,
+        bias_mutate_rate, bias_mutate_power, bias_replace_rate, 
+        bias_min_value, bias_max_value);
+    float_attribute!(response, response_init_mean, response_init_stdev,
+        response_init_type, response_mutate_rate, response_mutate_power,
+        response_replace_rate, response_min_value, response_max_value);
+    float_attribute!(time_constant, time_constant_init_mean, 
+        time_constant_init_stdev, time_constant_init_type, 
+        time_constant_mutate_rate, time_constant_mutate_power,
+        time_constant_replace_rate, time_constant_min_value, 
+        time_constant_max_value);
+    float_attribute!(iz_a, a_init_mean, a_init_stdev, a_init_type, 
+        a_mutate_rate, a_mutate_power, a_replace_rate, a_min_value, 
+        a_max_value);
+    float_attribute!(iz_b, b_init_mean, b_init_stdev, b_init_type, 
+        b_mutate_rate, b_mutate_power, b_replace_rate, b_min_value, 
+        b_max_value);
+    float_attribute!(iz_c, c_init_mean, c_init_stdev, c_init_type, 
+        c_mutate_rate, c_mutate_power, c_replace_rate, c_min_value, 
+        c_max_value);
+    float_attribute!(iz_d, d_init_mean, d_init_stdev, d_init_type, 
+        d_mutate_rate, d_mutate_power, d_replace_rate, d_min_value, 
+        d_max_value);
+    float_attribute!(weight, weight_init_mean, weight_init_stdev, 
+        weight_init_type, weight_mutate_rate, weight_mutate_power, 
+        weight_replace_rate, weight_min_value, weight_max_value);
+
+    /// Draws a fresh value from the init distribution, clamped to 
+    /// `min..=max`.
+    pub(crate) fn init_value<R: Rng>(&self, rng: &mut R) -> f64 {
+        let v = match self.init_type {
+            InitType::Gaussian => Normal::new(self.init_mean, self.init_stdev)
+                .unwrap()
+                .sample(rng),
+            InitType::Uniform => {
+                let lo = self.min.max(self.init_mean - 2.0 * self.init_stdev);
+                let hi = self.max.min(self.init_mean + 2.0 * self.init_stdev);
+                if lo < hi { rng.gen_range(lo..=hi) } else { lo }
+            }
+        };
+        v.clamp(self.min, self.max)
+    }
+
+    /// Perturbs `value` by gaussian noise with probability `mutate_rate`, or
+    /// replaces it by a fresh value from the init distribution with 
+    /// probability `replace_rate`.
+    pub(crate) fn mutate_value<R: Rng>(&self, value: f64, rng: &mut R) 
+            -> f64 {
+        let r = rng.gen_range(0.0..=1.0);
+        if r < self.mutate_rate {
+            let normal = Normal::new(0.0, self.mutate_power).unwrap();
+            (value + normal.sample(rng)).clamp(self.min, self.max)
+        } else if r < self.mutate_rate + self.replace_rate {
+            self.init_value(rng)
+        } else {
+            value
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeGene {
     id: usize,
 
     bias: f64,
     response: f64,
+    time_constant: f64,
+
+    // parameters of the node when run as an Izhikevich neuron
+    iz_a: f64,
+    iz_b: f64,
+    iz_c: f64,
+    iz_d: f64,
 
     activation: ActivationFunction,
     aggregation: AggregationFunction,
@@ -21,95 +122,156 @@ pub(crate) struct NodeGene {
 }
 
 impl NodeGene {
-    pub fn new(id: usize) -> Self {
+    /// Creates a node whose attributes are drawn from the init distributions
+    /// of `config`, with the default activation and aggregation functions.
+    pub fn new<R: Rng>(id: usize, config: &NeatConfig, rng: &mut R) -> Self {
         Self {
             id,
-            bias: 1.0,
-            response: 1.0,
-            aggregation: AggregationFunction::Sum,
-            activation: ActivationFunction::Sigmoid,
+            bias: FloatAttribute::bias(config).init_value(rng),
+            response: FloatAttribute::response(config).init_value(rng),
+            time_constant: FloatAttribute::time_constant(config)
+                .init_value(rng),
+            iz_a: FloatAttribute::iz_a(config).init_value(rng),
+            iz_b: FloatAttribute::iz_b(config).init_value(rng),
+            iz_c: FloatAttribute::iz_c(config).init_value(rng),
+            iz_d: FloatAttribute::iz_d(config).init_value(rng),
+            aggregation: config.aggregation_default.unwrap_or_else(|| 
+                *config.aggregation_options.choose(rng).unwrap()),
+            activation: config.activation_default.unwrap_or_else(|| 
+                *config.activation_options.choose(rng).unwrap()),
             src_connections: HashSet::new(),
             dst_connections: HashSet::new(),
         }
     }
 
+    /// Output of the node for the weighted inputs `vals`, 
+    /// `activation(bias + response * aggregation(vals))`.
     pub fn activate(&self, vals: &[f64]) -> f64 {
-        self.bias + self.response * 
-            self.activation.activate(self.aggregate(vals))
+        self.activation.activate(self.bias + 
+            self.response * self.aggregate(vals))
     }
 
     pub fn aggregate(&self, vals: &[f64]) -> f64 {
         self.aggregation.aggregate(vals)
     }
 
-    pub fn mutate(&mut self) {
-        let mut rng = thread_rng();
+    pub fn mutate<R: Rng>(&mut self, config: &NeatConfig, rng: &mut R) {
+        self.bias = FloatAttribute::bias(config).mutate_value(self.bias, rng);
+        self.response = FloatAttribute::response(config)
+            .mutate_value(self.response, rng);
+        self.time_constant = FloatAttribute::time_constant(config)
+            .mutate_value(self.time_constant, rng);
 
-        let r = rng.gen_range(0.0..=1.0);
-        if r < config::BIAS_MUTATE_RATE {
-            let normal = Normal::new(0.0, config::BIAS_MUTATE_POWER).unwrap();
-            self.bias = (self.bias + normal.sample(&mut rng))
-                .clamp(config::BIAS_MIN_VALUE, config::BIAS_MAX_VALUE);
-        } else if r < config::BIAS_MUTATE_RATE + config::BIAS_REPLACE_RATE {
-            self.bias = (random::<f64>() % 
-                (config::BIAS_MIN_VALUE + config::BIAS_MAX_VALUE)) - 
-                config::BIAS_MIN_VALUE;
-        }
+        self.iz_a = FloatAttribute::iz_a(config).mutate_value(self.iz_a, rng);
+        self.iz_b = FloatAttribute::iz_b(config).mutate_value(self.iz_b, rng);
+        self.iz_c = FloatAttribute::iz_c(config).mutate_value(self.iz_c, rng);
+        self.iz_d = FloatAttribute::iz_d(config).mutate_value(self.iz_d, rng);
 
         let r = rng.gen_range(0.0..=1.0);
-        if r < config::RESPONSE_MUTATE_RATE {
-            let normal = Normal::new(0.0, config::RESPONSE_MUTATE_POWER)
-                .unwrap();
-            self.response = (self.response + normal.sample(&mut rng))
-                .clamp(config::RESPONSE_MIN_VALUE, config::RESPONSE_MAX_VALUE);
-        } else if r < config::RESPONSE_MUTATE_RATE + 
-                config::RESPONSE_REPLACE_RATE {
-            self.response = (random::<f64>() % 
-                (config::RESPONSE_MIN_VALUE + config::RESPONSE_MAX_VALUE)) - 
-                config::RESPONSE_MIN_VALUE;
+        if r < config.activation_mutate_rate {
+            self.activation = *config.activation_options.choose(rng).unwrap();
         }
 
         let r = rng.gen_range(0.0..=1.0);
-        if r < config::ACTIVATION_MUT_PROB {
-            self.activation = random();
-        }
-
-        let r = rng.gen_range(0.0..=1.0);
-        if r < config::AGGREGATION_MUT_PROB {
-            self.aggregation = random();
+        if r < config.aggregation_mutate_rate {
+            self.aggregation = 
+                *config.aggregation_options.choose(rng).unwrap();
         }
     }
 
-    pub fn crossover(&self, other: &Self) -> Self {
-        let b = random::<f64>() % 1.0;
-        let ag = random::<f64>() % 1.0;
-        let ac = random::<f64>() % 1.0;
-        let mut ret = Self::new(self.id);
+    pub fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Self {
+        let mut ret = self.clone();
+        ret.src_connections.clear();
+        ret.dst_connections.clear();
+        let mut pick = |a: f64, b: f64| if rng.gen_bool(0.5) { b } else { a };
 
-        ret.bias = if b < 0.5 { other.bias } else { self.bias };
+        ret.bias = pick(self.bias, other.bias);
+        ret.response = pick(self.response, other.response);
+        ret.time_constant = pick(self.time_constant, other.time_constant);
+        ret.iz_a = pick(self.iz_a, other.iz_a);
+        ret.iz_b = pick(self.iz_b, other.iz_b);
+        ret.iz_c = pick(self.iz_c, other.iz_c);
+        ret.iz_d = pick(self.iz_d, other.iz_d);
         ret.activation = 
-            if ac < 0.5 { other.activation } else { self.activation };
+            [self.activation, other.activation][rng.gen_range(0..2)];
         ret.aggregation = 
-            if ag < 0.5 { other.aggregation } else { self.aggregation };
+            [self.aggregation, other.aggregation][rng.gen_range(0..2)];
         ret
     }
 
-    pub fn distance(&self, other: &Self) -> f64 {
+    pub fn distance(&self, other: &Self, config: &NeatConfig) -> f64 {
         let mut d = (self.bias - other.bias).abs() +
-            (self.response - other.response).abs();
+            (self.response - other.response).abs() +
+            (self.time_constant - other.time_constant).abs() +
+            (self.iz_a - other.iz_a).abs() + (self.iz_b - other.iz_b).abs() +
+            (self.iz_c - other.iz_c).abs() + (self.iz_d - other.iz_d).abs();
         if self.activation != other.activation { d += 1.0; }
         if self.aggregation != other.aggregation { d += 1.0; }
-        d * config::COMPAT_WEIGHT_COEFFICIENT
+        d * config.compatibility_weight_coefficient
     }
 
     #[inline]
     pub fn get_id(&self) -> usize {
         self.id
     }
+
+    #[inline]
+    pub fn get_bias(&self) -> f64 {
+        self.bias
+    }
+
+    #[inline]
+    pub fn set_bias(&mut self, bias: f64) {
+        self.bias = bias;
+    }
+
+    #[inline]
+    pub fn get_response(&self) -> f64 {
+        self.response
+    }
+
+    #[inline]
+    pub fn set_response(&mut self, response: f64) {
+        self.response = response;
+    }
+
+    /// Time constant of the node when it is run as part of a
+    /// [`CTRNN`](crate::nn::CTRNN).
+    #[inline]
+    pub fn get_time_constant(&self) -> f64 {
+        self.time_constant
+    }
+
+    /// The `a`, `b`, `c` and `d` parameters of the node when it is run as an
+    /// Izhikevich neuron in an [`IZNN`](crate::nn::IZNN).
+    #[inline]
+    pub fn get_izhikevich_params(&self) -> (f64, f64, f64, f64) {
+        (self.iz_a, self.iz_b, self.iz_c, self.iz_d)
+    }
+
+    #[inline]
+    pub fn get_activation(&self) -> ActivationFunction {
+        self.activation
+    }
+
+    #[inline]
+    pub fn set_activation(&mut self, activation: ActivationFunction) {
+        self.activation = activation;
+    }
+
+    #[inline]
+    pub fn get_aggregation(&self) -> AggregationFunction {
+        self.aggregation
+    }
+
+    #[inline]
+    pub fn set_aggregation(&mut self, aggregation: AggregationFunction) {
+        self.aggregation = aggregation;
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub (crate) struct ConnectionGene {
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionGene {
     id: usize,
     weight: f64,
     enabled: bool,
@@ -118,6 +280,17 @@ pub (crate) struct ConnectionGene {
 }
 
 impl ConnectionGene {
+    /// Draws an initial weight from the configured weight distribution.
+    pub fn init_weight<R: Rng>(config: &NeatConfig, rng: &mut R) -> f64 {
+        FloatAttribute::weight(config).init_value(rng)
+    }
+
+    /// Whether a new connection starts out enabled, as configured by
+    /// `enabled_default`.
+    pub fn init_enabled<R: Rng>(config: &NeatConfig, rng: &mut R) -> bool {
+        config.enabled_default.unwrap_or_else(|| rng.gen())
+    }
+
     pub fn new(id: usize, src: usize, dst: usize, weight: f64, enabled: bool)
             -> Self {
         Self {
@@ -129,45 +302,41 @@ impl ConnectionGene {
         }
     }
 
-    pub fn distance(&self, other: &Self) -> f64 {
+    pub fn distance(&self, other: &Self, config: &NeatConfig) -> f64 {
         let mut d = (self.weight - other.weight).abs();
         if self.enabled != other.enabled { d += 1.0; }
-        d * config::COMPAT_WEIGHT_COEFFICIENT
+        d * config.compatibility_weight_coefficient
     }
 
-    pub fn crossover(&self, other: &Self) -> Self {
+    pub fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Self {
         assert_eq!(self.id, other.id);
-        let w = random::<f64>() % 1.0;
-        let e = random::<f64>() % 1.0;
-
-        let weight = if w < 0.5 { other.weight } else { self.weight }; 
-        let enabled = if e < 0.5 { other.enabled } else { self.enabled };
+        let weight = if rng.gen_bool(0.5) { other.weight } else { self.weight };
+        let enabled = 
+            if rng.gen_bool(0.5) { other.enabled } else { self.enabled };
 
         Self::new(self.id, self.src, self.dst, weight, enabled)
     }
 
-    pub fn mutate(&mut self) {
-        let mut rng = thread_rng();
+    pub fn mutate<R: Rng>(&mut self, config: &NeatConfig, rng: &mut R) {
+        self.weight = FloatAttribute::weight(config)
+            .mutate_value(self.weight, rng);
 
+        let rate = config.enabled_mutate_rate + if self.enabled {
+            config.enabled_rate_to_false_add
+        } else {
+            config.enabled_rate_to_true_add
+        };
         let r = rng.gen_range(0.0..=1.0);
-        if r < config::WEIGHT_MUTATE_RATE {
-            let normal = Normal::new(0.0, config::WEIGHT_MUTATE_POWER)
-                .unwrap();
-            self.weight = (self.weight + normal.sample(&mut rng))
-                .clamp(config::WEIGHT_MIN_VALUE, config::WEIGHT_MAX_VALUE);
-        } else if r < config::WEIGHT_MUTATE_RATE + 
-                config::WEIGHT_REPLACE_RATE {
-            self.weight = (random::<f64>() % 
-                (config::WEIGHT_MIN_VALUE + config::WEIGHT_MAX_VALUE)) - 
-                config::WEIGHT_MIN_VALUE;
-        }
-
-        let r = rng.gen_range(0.0..=1.0);
-        if r < config::ENABLE_PROB {
+        if r < rate {
             self.enabled ^= true;
         }
     }
 
+    #[inline]
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
     #[inline]
     pub fn get_src(&self) -> usize {
         self.src
@@ -183,8 +352,103 @@ impl ConnectionGene {
         self.weight
     }
 
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    #[inline]
+    pub fn enable(&mut self) {
+        self.enabled = true;
+    }
+
     #[inline]
     pub fn disable(&mut self) {
         self.enabled = false;
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    #[test]
+    fn replacement_draws_from_init_distribution() {
+        let config = NeatConfig {
+            bias_init_mean: 0.25,
+            bias_init_stdev: 0.0,
+            bias_mutate_rate: 0.0,
+            bias_replace_rate: 1.0,
+            weight_init_mean: -0.5,
+            weight_init_stdev: 0.0,
+            weight_mutate_rate: 0.0,
+            weight_replace_rate: 1.0,
+            ..NeatConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        let mut node = NodeGene::new(0, &config, &mut rng);
+        node.set_bias(20.0);
+        node.mutate(&config, &mut rng);
+        assert_eq!(node.get_bias(), 0.25);
+
+        let mut conn = ConnectionGene::new(0, 0, 1, 20.0, true);
+        conn.mutate(&config, &mut rng);
+        assert_eq!(conn.get_weight(), -0.5);
+    }
+
+    #[test]
+    fn replaced_values_stay_near_init_mean() {
+        let config = NeatConfig {
+            weight_mutate_rate: 0.0,
+            weight_replace_rate: 1.0,
+            ..NeatConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut conn = ConnectionGene::new(0, 0, 1, 0.0, true);
+        let mut sum = 0.0;
+        for _ in 0..1000 {
+            conn.mutate(&config, &mut rng);
+            sum += conn.get_weight().abs();
+        }
+        // the mean absolute value of N(0, 1) is about 0.8, of a uniform 
+        // draw from -30..30 it is 15
+        assert!(sum / 1000.0 < 1.0);
+    }
+
+    #[test]
+    fn mutation_respects_bounds() {
+        let config = NeatConfig {
+            weight_mutate_rate: 1.0,
+            weight_mutate_power: 100.0,
+            weight_min_value: -1.0,
+            weight_max_value: 1.0,
+            ..NeatConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut conn = ConnectionGene::new(0, 0, 1, 0.0, true);
+        for _ in 0..100 {
+            conn.mutate(&config, &mut rng);
+            assert!((-1.0..=1.0).contains(&conn.get_weight()));
+        }
+    }
+
+    #[test]
+    fn activation_is_applied_after_bias_and_response() {
+        let config = NeatConfig {
+            activation_default: Some(ActivationFunction::Relu),
+            aggregation_default: Some(AggregationFunction::Sum),
+            ..NeatConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut node = NodeGene::new(0, &config, &mut rng);
+        node.set_bias(-1.0);
+        node.set_response(2.0);
+        assert_eq!(node.activate(&[0.25, 0.25]), 0.0);
+        assert_eq!(node.activate(&[1.0, 0.5]), 2.0);
+    }
+}
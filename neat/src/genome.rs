@@ -1,102 +1,282 @@
 use hashbrown::{HashMap, HashSet};
-use rand::{random, thread_rng, seq::IteratorRandom};
-
-use crate::{gene::{NodeGene, ConnectionGene}, config};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    gene::{NodeGene, ConnectionGene},
+    config::{InitialConnection, NeatConfig},
+    innovation::InnovationTracker,
+};
+
+/// Keys of `map` in ascending order. Random choices are made from this list
+/// rather than from the map itself, whose iteration order differs between
+/// runs.
+fn sorted_keys<V>(map: &HashMap<usize, V>) -> Vec<usize> {
+    let mut keys: Vec<usize> = map.keys().copied().collect();
+    keys.sort_unstable();
+    keys
+}
 
+/// A network encoded as node and connection genes. Genomes serialize with
+/// serde, e.g. to store the winner of a run as JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Genome {
-    id:         usize,
-    innovation: usize,
-    fitness:    f64,
+    id:      usize,
+    fitness: f64,
+
+    input_keys:  Vec<usize>,
+    output_keys: Vec<usize>,
 
     nodes:       HashMap<usize, NodeGene>,
     connections: HashMap<usize, ConnectionGene>,
 }
 
 impl Genome {
-    pub fn new(id: usize) -> Self {
+    /// Creates a genome with `config.num_inputs` input nodes, keyed 
+    /// `0..num_inputs`, followed by `config.num_outputs` output nodes and
+    /// `config.num_hidden` hidden nodes. The connections are laid out as
+    /// requested by `config.initial_connection`.
+    pub fn new<R: Rng>(id: usize, config: &NeatConfig, rng: &mut R) -> Self {
+        let num_keys = config.num_inputs + config.num_outputs;
         let mut ret = Genome {
             id,
-            innovation: 2,
             fitness: 0.0,
+            input_keys: (0..config.num_inputs).collect(),
+            output_keys: (config.num_inputs..num_keys).collect(),
             nodes: HashMap::new(),
             connections: HashMap::new()
         };
 
-        ret.connections.insert(0, ConnectionGene::new(0, 0, 4, 1.0, true));
-        ret.connections.insert(1, ConnectionGene::new(1, 1, 4, 1.0, true));
-        ret.connections.insert(2, ConnectionGene::new(2, 2, 4, 1.0, true));
-        ret.connections.insert(3, ConnectionGene::new(3, 3, 4, 1.0, true));
-
-        ret.nodes.insert(0, NodeGene::new(0));
-        (*ret.nodes.get_mut(&0).unwrap()).dst_connections.insert(0);
-        
-        ret.nodes.insert(1, NodeGene::new(1));
-        (*ret.nodes.get_mut(&1).unwrap()).dst_connections.insert(1);
-        
-        ret.nodes.insert(2, NodeGene::new(2));
-        (*ret.nodes.get_mut(&2).unwrap()).dst_connections.insert(2);
-        
-        ret.nodes.insert(3, NodeGene::new(3));
-        (*ret.nodes.get_mut(&3).unwrap()).dst_connections.insert(3);
-
-        ret.nodes.insert(4, NodeGene::new(4));
-        (*ret.nodes.get_mut(&4).unwrap()).src_connections.insert(0);
-        (*ret.nodes.get_mut(&4).unwrap()).src_connections.insert(1);
-        (*ret.nodes.get_mut(&4).unwrap()).src_connections.insert(2);
-        (*ret.nodes.get_mut(&4).unwrap()).src_connections.insert(3);
+        for key in 0..num_keys + config.num_hidden {
+            ret.nodes.insert(key, NodeGene::new(key, config, rng));
+        }
+        ret.connect_initial(config, rng);
 
+        ret
+    }
 
+    /// All connections that a fully connected genome would start out with,
+    /// flagged if they connect an input directly to an output despite hidden
+    /// nodes. The position in this list is used as the innovation number, so
+    /// the initial connections of all genomes line up no matter which subset
+    /// of them is enabled.
+    pub(crate) fn initial_candidates(config: &NeatConfig) 
+            -> Vec<(usize, usize, bool)> {
+        let num_keys = config.num_inputs + config.num_outputs;
+        let inputs = 0..config.num_inputs;
+        let outputs = config.num_inputs..num_keys;
+        let hidden = num_keys..num_keys + config.num_hidden;
+
+        let mut ret = Vec::new();
+        for src in inputs.clone() {
+            for dst in hidden.clone() {
+                ret.push((src, dst, false));
+            }
+        }
+        for src in hidden.clone() {
+            for dst in outputs.clone() {
+                ret.push((src, dst, false));
+            }
+        }
+        for src in inputs {
+            for dst in outputs.clone() {
+                ret.push((src, dst, !hidden.is_empty()));
+            }
+        }
+        // recurrent genomes also start with self-connections
+        if !config.feed_forward {
+            for key in outputs.chain(hidden) {
+                ret.push((key, key, false));
+            }
+        }
         ret
     }
 
-    fn get_new_node_key(&self) -> usize {
-        let mut r = random();
-        while self.nodes.contains_key(&r) {
-            r = random();
+    fn connect_initial<R: Rng>(&mut self, config: &NeatConfig, rng: &mut R) {
+        let candidates = Self::initial_candidates(config);
+
+        let direct = |&(_, _, d): &(usize, usize, bool)| d;
+        let selected: Vec<usize> = match config.initial_connection {
+            InitialConnection::Unconnected => Vec::new(),
+            InitialConnection::FsNeatNoHidden | 
+            InitialConnection::FsNeatHidden => {
+                let hidden = config.initial_connection == 
+                    InitialConnection::FsNeatHidden;
+                let input = *self.input_keys.choose(rng).unwrap();
+                (0..candidates.len())
+                    .filter(|&i| {
+                        let (src, dst, _) = candidates[i];
+                        src == input && (hidden || self.is_output(dst))
+                    })
+                    .collect()
+            }
+            InitialConnection::FullNoDirect => (0..candidates.len())
+                .filter(|&i| !direct(&candidates[i]))
+                .collect(),
+            InitialConnection::FullDirect => (0..candidates.len()).collect(),
+            InitialConnection::PartialNoDirect(p) => {
+                let mut all: Vec<usize> = (0..candidates.len())
+                    .filter(|&i| !direct(&candidates[i]))
+                    .collect();
+                all.shuffle(rng);
+                all.truncate((all.len() as f64 * p).round() as usize);
+                all
+            }
+            InitialConnection::PartialDirect(p) => {
+                let mut all: Vec<usize> = (0..candidates.len()).collect();
+                all.shuffle(rng);
+                all.truncate((all.len() as f64 * p).round() as usize);
+                all
+            }
+        };
+
+        for key in selected {
+            let (src, dst, _) = candidates[key];
+            let weight = ConnectionGene::init_weight(config, rng);
+            let enabled = ConnectionGene::init_enabled(config, rng);
+            self.insert_connection(key, src, dst, weight, enabled);
         }
-        r
     }
 
     #[inline]
-    fn get_new_conn_key(&mut self) -> usize {
-        self.innovation += 1;
-        self.innovation
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    #[inline]
+    pub fn get_fitness(&self) -> f64 {
+        self.fitness
+    }
+
+    #[inline]
+    pub fn set_fitness(&mut self, fitness: f64) {
+        self.fitness = fitness;
+    }
+
+    #[inline]
+    pub fn input_keys(&self) -> &[usize] {
+        &self.input_keys
+    }
+
+    #[inline]
+    pub fn output_keys(&self) -> &[usize] {
+        &self.output_keys
+    }
+
+    #[inline]
+    pub fn nodes(&self) -> &HashMap<usize, NodeGene> {
+        &self.nodes
     }
 
-    fn add_connection(&mut self, src: usize, dst: usize, weight: f64, 
-            enabled: bool) {
-        let new_id = self.get_new_conn_key();
-        self.connections.insert(new_id, ConnectionGene::new(
-            new_id, src, dst, weight, enabled));
-        (*self.nodes.get_mut(&src).unwrap()).dst_connections.insert(new_id);
-        (*self.nodes.get_mut(&dst).unwrap()).src_connections.insert(new_id);
+    #[inline]
+    pub fn connections(&self) -> &HashMap<usize, ConnectionGene> {
+        &self.connections
     }
 
-    pub fn crossover(&mut self, genome1: &Self, genome2: &Self) {
+    #[inline]
+    pub fn node_mut(&mut self, key: usize) -> Option<&mut NodeGene> {
+        self.nodes.get_mut(&key)
+    }
+
+    /// Inserts a fresh hidden node under `key`, which must not be in use yet.
+    /// Using the same key in every genome keeps hand-made nodes aligned
+    /// during crossover.
+    pub fn add_node<R: Rng>(&mut self, key: usize, config: &NeatConfig, 
+            rng: &mut R) -> &mut NodeGene {
+        assert!(!self.nodes.contains_key(&key), "node {} already exists", key);
+        self.nodes.entry(key).or_insert(NodeGene::new(key, config, rng))
+    }
+
+    #[inline]
+    fn is_input(&self, key: usize) -> bool {
+        self.input_keys.contains(&key)
+    }
+
+    #[inline]
+    fn is_output(&self, key: usize) -> bool {
+        self.output_keys.contains(&key)
+    }
+
+    /// Adds a connection under the innovation number `innovations` assigns
+    /// to it and returns that number.
+    pub fn add_connection(&mut self, innovations: &mut InnovationTracker, 
+            src: usize, dst: usize, weight: f64, enabled: bool) -> usize {
+        let new_id = innovations.get_connection_key(src, dst);
+        self.insert_connection(new_id, src, dst, weight, enabled);
+        new_id
+    }
+
+    pub fn remove_connection(&mut self, id: usize) -> Option<ConnectionGene> {
+        let conn = self.connections.remove(&id)?;
+        self.nodes.get_mut(&conn.get_src()).unwrap().dst_connections
+            .remove(&id);
+        self.nodes.get_mut(&conn.get_dst()).unwrap().src_connections
+            .remove(&id);
+        Some(conn)
+    }
+
+    fn insert_connection(&mut self, id: usize, src: usize, dst: usize, 
+            weight: f64, enabled: bool) {
+        self.connections.insert(id, ConnectionGene::new(
+            id, src, dst, weight, enabled));
+        self.nodes.get_mut(&src).unwrap().dst_connections.insert(id);
+        self.nodes.get_mut(&dst).unwrap().src_connections.insert(id);
+    }
+
+    /// Creates a child of the two genomes. Matching genes are inherited
+    /// randomly from either parent, disjoint and excess genes from the 
+    /// fitter one.
+    pub fn crossover<R: Rng>(id: usize, genome1: &Self, genome2: &Self, 
+            rng: &mut R) -> Self {
         let (parent1, parent2) = if genome1.fitness > genome2.fitness { 
             (genome1, genome2) 
         } else { 
             (genome2, genome1)
         };
 
-        for (k, conn1) in parent1.connections.iter() {
+        let mut ret = Genome {
+            id,
+            fitness: 0.0,
+            input_keys: parent1.input_keys.clone(),
+            output_keys: parent1.output_keys.clone(),
+            nodes: HashMap::new(),
+            connections: HashMap::new()
+        };
+
+        for k in sorted_keys(&parent1.connections) {
+            let conn1 = &parent1.connections[&k];
             match parent2.connections.get(&k) {
                 Some(conn2) => {
-                    self.connections.insert(*k, conn1.crossover(conn2));
+                    ret.connections.insert(k, conn1.crossover(conn2, rng));
                 }
-                None => { self.connections.insert(*k, *conn1); }
+                None => { ret.connections.insert(k, *conn1); }
             }
         }
 
-        for (k, node1) in parent1.nodes.iter() {
-            assert!(self.nodes.get(k).is_none());
+        for k in sorted_keys(&parent1.nodes) {
+            let node1 = &parent1.nodes[&k];
             match parent2.nodes.get(&k) {
                 Some(node2) => {
-                    self.nodes.insert(*k, node1.crossover(node2));
+                    ret.nodes.insert(k, node1.crossover(node2, rng));
                 }
-                None => { self.nodes.insert(*k, node1.clone()); }
+                None => { ret.nodes.insert(k, node1.clone()); }
             }
         }
+
+        // the connection sets of the nodes have to match the inherited 
+        // connections, not the ones of either parent
+        for node in ret.nodes.values_mut() {
+            node.src_connections.clear();
+            node.dst_connections.clear();
+        }
+        for (id, conn) in ret.connections.iter() {
+            ret.nodes.get_mut(&conn.get_src()).unwrap().dst_connections
+                .insert(*id);
+            ret.nodes.get_mut(&conn.get_dst()).unwrap().src_connections
+                .insert(*id);
+        }
+
+        ret
     }
 
     fn creates_circles(&self, src: usize, dst: usize) -> bool {
@@ -116,171 +296,374 @@ impl Genome {
                 }
             }
             if num_added == 0 {
-                return true;
+                return false;
             }
         }
     }
 
-    fn mutate_add_node(&mut self) {
-        let mut rng = thread_rng();
-        let new_node_id = self.get_new_node_key();
-        let new_node = NodeGene::new(new_node_id);
-        self.nodes.insert(new_node_id, new_node);
+    fn mutate_add_node<R: Rng>(&mut self, config: &NeatConfig, 
+            innovations: &mut InnovationTracker, rng: &mut R) {
+        let conn = match sorted_keys(&self.connections).choose(rng) {
+            Some(id) => self.connections.get_mut(id).unwrap(),
+            None => {
+                if config.check_structural_mutation_surer() {
+                    self.mutate_add_conn(config, innovations, rng);
+                }
+                return;
+            }
+        };
+
+        // this connection has already been split in this genome
+        let new_node_id = innovations.get_split_node_key(conn.get_id());
+        if self.nodes.contains_key(&new_node_id) {
+            return;
+        }
 
-        let conn = self.connections.values_mut().choose(&mut rng).unwrap();
-        (*conn).disable();
+        conn.disable();
         let src = conn.get_src();
         let dst = conn.get_dst();
         let weight = conn.get_weight();
 
-        self.add_connection(src, new_node_id, 1.0, true);
-        self.add_connection(new_node_id, dst, weight, true);
+        self.nodes.insert(new_node_id, 
+            NodeGene::new(new_node_id, config, rng));
+        self.add_connection(innovations, src, new_node_id, 1.0, true);
+        self.add_connection(innovations, new_node_id, dst, weight, true);
     }
 
-    fn mutate_add_conn(&mut self) {
-        let mut rng = thread_rng();
-        let mut out_node = self.nodes.values().choose(&mut rng).unwrap();
-        while out_node.get_id() < 4 {
-            out_node = self.nodes.values().choose(&mut rng).unwrap();
+    fn mutate_add_conn<R: Rng>(&mut self, config: &NeatConfig, 
+            innovations: &mut InnovationTracker, rng: &mut R) {
+        let keys = sorted_keys(&self.nodes);
+        let mut out_node = &self.nodes[keys.choose(rng).unwrap()];
+        while self.is_input(out_node.get_id()) {
+            out_node = &self.nodes[keys.choose(rng).unwrap()];
         }
 
-        let mut in_node = self.nodes.values().choose(&mut rng).unwrap();
-        while out_node.get_id() == 4 && in_node.get_id() == 4 {
-            in_node = self.nodes.values().choose(&mut rng).unwrap();
+        // outputs of feed-forward genomes are never connected to each other
+        let mut in_node = &self.nodes[keys.choose(rng).unwrap()];
+        while config.feed_forward && self.is_output(out_node.get_id()) && 
+                self.is_output(in_node.get_id()) {
+            in_node = &self.nodes[keys.choose(rng).unwrap()];
         }
 
         // return if this connection already exists
-        if in_node.dst_connections.iter().any(|x| {
-            self.connections.get(x).unwrap().get_dst() == out_node.get_id()
-        }) {
+        let existing = in_node.dst_connections.iter()
+            .copied()
+            .find(|x| self.connections[x].get_dst() == out_node.get_id());
+        if let Some(id) = existing {
+            if config.check_structural_mutation_surer() {
+                self.connections.get_mut(&id).unwrap().enable();
+            }
             return;
         }
 
-        if self.creates_circles(in_node.get_id(), out_node.get_id()) {
+        if config.feed_forward && 
+                self.creates_circles(in_node.get_id(), out_node.get_id()) {
             return;
         }
 
-        let min = config::WEIGHT_MIN_VALUE;
-        let max = config::WEIGHT_MAX_VALUE;
-        let weight = (random::<f64>() % (min + max)) - min;
-        self.add_connection(in_node.get_id(), out_node.get_id(), weight, true);
+        let weight = ConnectionGene::init_weight(config, rng);
+        let enabled = ConnectionGene::init_enabled(config, rng);
+        self.add_connection(innovations, in_node.get_id(), out_node.get_id(),
+            weight, enabled);
     }
 
-    fn mutate_del_node(&mut self) {
-        let mut rng = thread_rng();
-        let node = self.nodes.keys().filter(|&&x| x > 4).choose(&mut rng);
-        if node.is_none() {
-            return;
-        }
-        let node = node.unwrap();
+    fn mutate_del_node<R: Rng>(&mut self, rng: &mut R) {
+        let hidden: Vec<usize> = sorted_keys(&self.nodes).into_iter()
+            .filter(|&x| !self.is_input(x) && !self.is_output(x))
+            .collect();
+        let node = match hidden.choose(rng) {
+            Some(&node) => node,
+            None => return,
+        };
 
-        for conn_id in self.nodes.get(node).unwrap().src_connections.iter() {
-            let conn = self.connections.get(conn_id).unwrap();
-            self.nodes.get_mut(&conn.get_src()).unwrap().dst_connections
-                .remove(conn_id);
-            self.connections.remove(conn_id);
-        }
+        let node = self.nodes.remove(&node).unwrap();
 
-        for conn_id in self.nodes.get(node).unwrap().dst_connections.iter() {
-            let conn = self.connections.get(conn_id).unwrap();
-            self.nodes.get_mut(&conn.get_dst()).unwrap().dst_connections
-                .remove(conn_id);
-            self.connections.remove(conn_id);
+        // self-connections show up in both sets, so they may already be gone
+        for conn_id in node.src_connections.iter() {
+            let conn = match self.connections.remove(conn_id) {
+                Some(conn) => conn,
+                None => continue,
+            };
+            if let Some(src) = self.nodes.get_mut(&conn.get_src()) {
+                src.dst_connections.remove(conn_id);
+            }
         }
 
-        self.nodes.remove(node);
+        for conn_id in node.dst_connections.iter() {
+            let conn = match self.connections.remove(conn_id) {
+                Some(conn) => conn,
+                None => continue,
+            };
+            if let Some(dst) = self.nodes.get_mut(&conn.get_dst()) {
+                dst.src_connections.remove(conn_id);
+            }
+        }
     }
 
-    fn mutate_del_conn(&mut self) {
-        let mut rng = thread_rng();
-        let (id, conn) = self.connections.iter().choose(&mut rng).unwrap();
-        self.nodes.get_mut(&conn.get_src()).unwrap().dst_connections.remove(id);
-        self.nodes.get_mut(&conn.get_dst()).unwrap().src_connections.remove(id);
-        self.connections.remove(id);
+    fn mutate_del_conn<R: Rng>(&mut self, rng: &mut R) {
+        let id = match sorted_keys(&self.connections).choose(rng) {
+            Some(&id) => id,
+            None => return,
+        };
+        self.remove_connection(id);
     }
 
-    pub fn mutate(&mut self) {
-        if config::SINGLE_MUTATION {
-            let node_add_prob =                 config::NODE_ADD_PROB;
-            let node_del_prob = node_add_prob + config::NODE_DEL_PROB;
-            let conn_add_prob = node_del_prob + config::CONN_ADD_PROB;
-            let conn_del_prob = conn_add_prob + config::CONN_DEL_PROB;
+    /// Applies structural mutations, numbered by `innovations`, followed by
+    /// mutations of the attributes of every gene.
+    pub fn mutate<R: Rng>(&mut self, config: &NeatConfig, 
+            innovations: &mut InnovationTracker, rng: &mut R) {
+        if config.single_structural_mutation {
+            let node_add_prob =                 config.node_add_prob;
+            let node_del_prob = node_add_prob + config.node_delete_prob;
+            let conn_add_prob = node_del_prob + config.conn_add_prob;
+            let conn_del_prob = conn_add_prob + config.conn_delete_prob;
             let div = conn_del_prob.max(1.0);
 
-            let r = random::<f64>() % 1.0;
+            let r = rng.gen::<f64>();
             if r < node_add_prob/div {
-                self.mutate_add_node();
+                self.mutate_add_node(config, innovations, rng);
             } else if r < node_del_prob/div {
-                self.mutate_del_node();
+                self.mutate_del_node(rng);
             } else if r < conn_add_prob/div {
-                self.mutate_add_conn();
+                self.mutate_add_conn(config, innovations, rng);
             } else if r < conn_del_prob/div {
-                self.mutate_del_conn();
+                self.mutate_del_conn(rng);
             }
         } else {
-            if random::<f64>() % 1.0 < config::NODE_ADD_PROB {
-                self.mutate_add_node();
+            if rng.gen::<f64>() < config.node_add_prob {
+                self.mutate_add_node(config, innovations, rng);
             }
-            if random::<f64>() % 1.0 < config::NODE_DEL_PROB {
-                self.mutate_del_node();
+            if rng.gen::<f64>() < config.node_delete_prob {
+                self.mutate_del_node(rng);
             }
-            if random::<f64>() % 1.0 < config::CONN_ADD_PROB {
-                self.mutate_add_conn();
+            if rng.gen::<f64>() < config.conn_add_prob {
+                self.mutate_add_conn(config, innovations, rng);
             }
-            if random::<f64>() % 1.0 < config::CONN_DEL_PROB {
-                self.mutate_del_conn();
+            if rng.gen::<f64>() < config.conn_delete_prob {
+                self.mutate_del_conn(rng);
             }
         }
 
-        for conn in self.connections.values_mut() {
-            (*conn).mutate();
+        for k in sorted_keys(&self.connections) {
+            self.connections.get_mut(&k).unwrap().mutate(config, rng);
         }
 
-        for node in self.nodes.values_mut() {
-            (*node).mutate();
+        // input nodes are never evaluated, so their attributes are left alone
+        for k in sorted_keys(&self.nodes) {
+            if !self.is_input(k) {
+                self.nodes.get_mut(&k).unwrap().mutate(config, rng);
+            }
         }
     }
 
-    pub fn distance(&self, other: &Self) -> f64 {
+    /// Compatibility distance of two genomes. Input nodes are shared by all
+    /// genomes and never evaluated, so only the other nodes are compared.
+    pub fn distance(&self, other: &Self, config: &NeatConfig) -> f64 {
+        // summed in key order, so that the result does not depend on the
+        // iteration order of the maps
+        let mut nodes1: Vec<&NodeGene> = self.nodes.values()
+            .filter(|n| !self.is_input(n.get_id()))
+            .collect();
+        nodes1.sort_by_key(|n| n.get_id());
+        let nodes2: Vec<&NodeGene> = other.nodes.values()
+            .filter(|n| !other.is_input(n.get_id()))
+            .collect();
+
         let mut node_distance = 0.0;
-        if !self.nodes.is_empty() || !other.nodes.is_empty() {
+        if !nodes1.is_empty() || !nodes2.is_empty() {
             let mut disjoined_nodes = 0.0;
-            for n in other.nodes.keys().into_iter() {
-                if !self.nodes.contains_key(n) {
+            for n in nodes2.iter() {
+                if !self.nodes.contains_key(&n.get_id()) {
                     disjoined_nodes += 1.0;
                 }
             }
-            for (id, node) in self.nodes.into_iter() {
-                match other.nodes.get(&id) {
+            for node in nodes1.iter() {
+                match other.nodes.get(&node.get_id()) {
                     None => { disjoined_nodes += 1.0; }
-                    Some(n2) => { node_distance += node.distance(n2); }
+                    Some(n2) => { node_distance += node.distance(n2, config); }
                 }
             }
-            let max_nodes = self.nodes.len().max(other.nodes.len());
-            node_distance = (node_distance + config::COMPAT_DISJOINT_COEFFICIENT 
-                * disjoined_nodes) / max_nodes;
+            let max_nodes = nodes1.len().max(nodes2.len()) as f64;
+            node_distance = (node_distance + 
+                config.compatibility_disjoint_coefficient * disjoined_nodes) / 
+                max_nodes;
         }
 
         let mut connection_distance = 0.0;
         if !self.connections.is_empty() || !other.connections.is_empty() {
             let mut disjoined_connections = 0.0;
-            for c in other.connections.keys().into_iter() {
+            for c in other.connections.keys() {
                 if !self.connections.contains_key(c) {
-                    connection_distance += 1.0;
+                    disjoined_connections += 1.0;
                 }
             }
-            for (id, conn) in self.connections.into_iter() {
-                match other.connections.get(&id) {
+            let mut connections: Vec<(&usize, &ConnectionGene)> = 
+                self.connections.iter().collect();
+            connections.sort_by_key(|&(id, _)| *id);
+            for (id, conn) in connections {
+                match other.connections.get(id) {
                     None => { disjoined_connections += 1.0; }
-                    Some(c2) => { connection_distance += conn.distance(c2); }
+                    Some(c2) => { 
+                        connection_distance += conn.distance(c2, config); 
+                    }
                 }
             }
-            let max_conn = self.connections.len().max(other.connections.len());
+            let max_conn = 
+                self.connections.len().max(other.connections.len()) as f64;
             connection_distance = (connection_distance + 
-                config::COMPAT_DISJOINT_COEFFICIENT * disjoined_connections) / 
-                max_conn;
+                config.compatibility_disjoint_coefficient * 
+                disjoined_connections) / max_conn;
         }
 
         node_distance + connection_distance
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn config(initial_connection: InitialConnection) -> NeatConfig {
+        NeatConfig {
+            num_inputs: 3,
+            num_outputs: 2,
+            num_hidden: 2,
+            initial_connection,
+            ..NeatConfig::default()
+        }
+    }
+
+    fn num_connections(initial_connection: InitialConnection) -> usize {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        Genome::new(0, &config(initial_connection), &mut rng)
+            .connections().len()
+    }
+
+    #[test]
+    fn initial_connection_modes() {
+        // 3 inputs to 2 hidden, 2 hidden to 2 outputs, 3 inputs to 2 outputs
+        assert_eq!(num_connections(InitialConnection::Unconnected), 0);
+        assert_eq!(num_connections(InitialConnection::FsNeatNoHidden), 2);
+        assert_eq!(num_connections(InitialConnection::FsNeatHidden), 4);
+        assert_eq!(num_connections(InitialConnection::FullNoDirect), 10);
+        assert_eq!(num_connections(InitialConnection::FullDirect), 16);
+        assert_eq!(num_connections(
+            InitialConnection::PartialNoDirect(0.5)), 5);
+        assert_eq!(num_connections(
+            InitialConnection::PartialDirect(0.25)), 4);
+    }
+
+    #[test]
+    fn full_no_direct_without_hidden_connects_inputs_to_outputs() {
+        let config = NeatConfig {
+            num_hidden: 0,
+            ..config(InitialConnection::FullNoDirect)
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let genome = Genome::new(0, &config, &mut rng);
+        assert_eq!(genome.connections().len(), 6);
+        assert!(genome.connections().values()
+            .all(|c| genome.is_input(c.get_src()) && 
+                genome.is_output(c.get_dst())));
+    }
+
+    #[test]
+    fn attributes_are_drawn_from_init_distributions() {
+        let config = NeatConfig {
+            bias_init_mean: 0.3,
+            bias_init_stdev: 0.0,
+            response_init_mean: -2.0,
+            response_init_stdev: 0.0,
+            weight_init_mean: 0.7,
+            weight_init_stdev: 0.0,
+            conn_add_prob: 1.0,
+            single_structural_mutation: false,
+            ..config(InitialConnection::Unconnected)
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&config);
+        let mut genome = Genome::new(0, &config, &mut rng);
+        for _ in 0..20 {
+            genome.mutate_add_conn(&config, &mut innovations, &mut rng);
+        }
+
+        assert!(!genome.connections().is_empty());
+        assert!(genome.connections().values()
+            .all(|c| c.get_weight() == 0.7));
+        assert!(genome.nodes().values()
+            .all(|n| n.get_bias() == 0.3 && n.get_response() == -2.0));
+    }
+
+    #[test]
+    fn surer_structural_mutation_falls_back_to_adding_a_connection() {
+        let config = NeatConfig {
+            structural_mutation_surer: Some(true),
+            ..config(InitialConnection::Unconnected)
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&config);
+        let mut genome = Genome::new(0, &config, &mut rng);
+        genome.mutate_add_node(&config, &mut innovations, &mut rng);
+        assert_eq!(genome.connections().len(), 1);
+
+        let config = NeatConfig {
+            structural_mutation_surer: Some(false),
+            ..config
+        };
+        let mut genome = Genome::new(0, &config, &mut rng);
+        genome.mutate_add_node(&config, &mut innovations, &mut rng);
+        assert!(genome.connections().is_empty());
+    }
+
+    #[test]
+    fn input_nodes_do_not_count_towards_distance() {
+        let config = NeatConfig {
+            bias_mutate_rate: 1.0,
+            bias_mutate_power: 5.0,
+            activation_mutate_rate: 1.0,
+            node_add_prob: 0.0,
+            node_delete_prob: 0.0,
+            conn_add_prob: 0.0,
+            conn_delete_prob: 0.0,
+            ..config(InitialConnection::FullDirect)
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&config);
+        let genome = Genome::new(0, &config, &mut rng);
+
+        let mut mutated = genome.clone();
+        mutated.mutate(&config, &mut innovations, &mut rng);
+        for &k in genome.input_keys() {
+            let (a, b) = (&genome.nodes()[&k], &mutated.nodes()[&k]);
+            assert_eq!(a.get_bias(), b.get_bias());
+            assert_eq!(a.get_activation(), b.get_activation());
+        }
+
+        // genomes that only differ in their input nodes are identical
+        let mut other = genome.clone();
+        for &k in genome.input_keys() {
+            other.node_mut(k).unwrap().set_bias(100.0);
+        }
+        assert_eq!(genome.distance(&other, &config), 0.0);
+    }
+
+    #[test]
+    fn uniform_init_stays_within_two_stdevs() {
+        let config = NeatConfig {
+            weight_init_mean: 1.0,
+            weight_init_stdev: 0.5,
+            weight_init_type: crate::config::InitType::Uniform,
+            ..config(InitialConnection::FullDirect)
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for id in 0..20 {
+            let genome = Genome::new(id, &config, &mut rng);
+            assert!(genome.connections().values()
+                .all(|c| (0.0..=2.0).contains(&c.get_weight())));
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Helpers for analysing the directed graph spanned by a genome's
+//! connections, given as `(src, dst)` pairs.
+
+use hashbrown::HashSet;
+
+/// Collects the nodes whose values are needed to compute the outputs. Input
+/// nodes are never part of the result, output nodes always are.
+pub fn required_for_output(inputs: &[usize], outputs: &[usize],
+        connections: &[(usize, usize)]) -> HashSet<usize> {
+    let mut required: HashSet<usize> = outputs.iter().copied().collect();
+    let mut visited = required.clone();
+    loop {
+        let layer: HashSet<usize> = connections.iter()
+            .filter(|(src, dst)| visited.contains(dst) && 
+                !visited.contains(src))
+            .map(|&(src, _)| src)
+            .collect();
+        if layer.is_empty() {
+            break;
+        }
+
+        let hidden: Vec<usize> = layer.iter().copied()
+            .filter(|n| !inputs.contains(n))
+            .collect();
+        if hidden.is_empty() {
+            break;
+        }
+
+        required.extend(hidden);
+        visited.extend(layer);
+    }
+
+    required
+}
+
+/// Groups the required nodes into layers that can be evaluated in order:
+/// every node only depends on the inputs and on nodes of earlier layers.
+/// Nodes without any incoming connection are placed in the first layer, nodes
+/// that are part of a cycle are left out.
+pub fn feed_forward_layers(inputs: &[usize], outputs: &[usize],
+        connections: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let required = required_for_output(inputs, outputs, connections);
+
+    let mut layers = Vec::new();
+    let mut visited: HashSet<usize> = inputs.iter().copied().collect();
+    loop {
+        let mut layer: Vec<usize> = required.iter().copied()
+            .filter(|n| !visited.contains(n) && connections.iter()
+                .filter(|(_, dst)| dst == n)
+                .all(|(src, _)| visited.contains(src)))
+            .collect();
+        if layer.is_empty() {
+            break;
+        }
+
+        layer.sort();
+        visited.extend(layer.iter().copied());
+        layers.push(layer);
+    }
+
+    layers
+}
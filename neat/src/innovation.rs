@@ -0,0 +1,213 @@
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::NeatConfig, genome::Genome};
+
+/// Hands out innovation numbers for connections and keys for new nodes, so
+/// that the same structural mutation gets the same number in every genome.
+///
+/// Connections are identified by their endpoints, nodes created by splitting
+/// a connection by the innovation number of that connection. Depending on
+/// `config.reset_innovations` this memory is cleared every generation or
+/// kept for the whole run; the counters themselves are never reset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InnovationTracker {
+    next_connection: usize,
+    next_node:       usize,
+
+    #[serde(with = "connection_entries")]
+    connections: HashMap<(usize, usize), usize>,
+    splits:      HashMap<usize, usize>,
+}
+
+/// JSON objects only have string keys, so connections are stored as a list
+/// of `[[src, dst], innovation]` entries instead.
+mod connection_entries {
+    use hashbrown::HashMap;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(map: &HashMap<(usize, usize), usize>,
+            serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) 
+            -> Result<HashMap<(usize, usize), usize>, D::Error> {
+        let entries: Vec<((usize, usize), usize)> = 
+            Vec::deserialize(deserializer)?;
+        Ok(entries.into_iter().collect())
+    }
+}
+
+impl InnovationTracker {
+    /// Starts numbering after the connections and nodes that
+    /// [`Genome::new`] creates.
+    pub fn new(config: &NeatConfig) -> Self {
+        let candidates = Genome::initial_candidates(config);
+        Self {
+            next_connection: candidates.len(),
+            next_node: config.num_inputs + config.num_outputs + 
+                config.num_hidden,
+            connections: candidates.iter().enumerate()
+                .map(|(i, &(src, dst, _))| ((src, dst), i))
+                .collect(),
+            splits: HashMap::new(),
+        }
+    }
+
+    /// Takes note of the innovations of a genome that was not created
+    /// through this tracker, so that no number in it is handed out again.
+    pub fn register(&mut self, genome: &Genome) {
+        for conn in genome.connections().values() {
+            self.connections.entry((conn.get_src(), conn.get_dst()))
+                .or_insert(conn.get_id());
+            self.next_connection = self.next_connection.max(conn.get_id() + 1);
+        }
+        for &key in genome.nodes().keys() {
+            self.next_node = self.next_node.max(key + 1);
+        }
+    }
+
+    /// Innovation number of the connection from `src` to `dst`.
+    pub fn get_connection_key(&mut self, src: usize, dst: usize) -> usize {
+        *self.connections.entry((src, dst)).or_insert_with(|| {
+            self.next_connection += 1;
+            self.next_connection - 1
+        })
+    }
+
+    /// Key of the node that is inserted when splitting the connection with
+    /// innovation number `connection`.
+    pub fn get_split_node_key(&mut self, connection: usize) -> usize {
+        *self.splits.entry(connection).or_insert_with(|| {
+            self.next_node += 1;
+            self.next_node - 1
+        })
+    }
+
+    /// Forgets the innovations of the current generation if 
+    /// `config.reset_innovations` is set.
+    pub fn next_generation(&mut self, config: &NeatConfig) {
+        if config.reset_innovations {
+            self.connections.clear();
+            self.splits.clear();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::config::InitialConnection;
+
+    fn config(reset_innovations: bool) -> NeatConfig {
+        NeatConfig {
+            num_inputs: 1,
+            num_outputs: 1,
+            initial_connection: InitialConnection::FullDirect,
+            node_add_prob: 1.0,
+            conn_add_prob: 0.0,
+            node_delete_prob: 0.0,
+            conn_delete_prob: 0.0,
+            reset_innovations,
+            ..NeatConfig::default()
+        }
+    }
+
+    fn sorted<T: Ord + Copy>(keys: impl Iterator<Item = T>) -> Vec<T> {
+        let mut keys: Vec<T> = keys.collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    #[test]
+    fn same_mutation_gets_same_numbers() {
+        let config = config(true);
+        let mut innovations = InnovationTracker::new(&config);
+        let mut genomes: Vec<Genome> = (0..2)
+            .map(|id| {
+                let mut rng = ChaCha8Rng::seed_from_u64(id as u64);
+                let mut genome = Genome::new(id, &config, &mut rng);
+                genome.mutate(&config, &mut innovations, &mut rng);
+                genome
+            })
+            .collect();
+
+        // the only connection was split into two new ones
+        let b = genomes.pop().unwrap();
+        let a = genomes.pop().unwrap();
+        assert_eq!(sorted(a.nodes().keys().copied()), [0, 1, 2]);
+        assert_eq!(sorted(b.nodes().keys().copied()), [0, 1, 2]);
+        assert_eq!(sorted(a.connections().keys().copied()), [0, 1, 2]);
+        assert_eq!(sorted(b.connections().keys().copied()), [0, 1, 2]);
+        for (id, conn) in a.connections() {
+            let other = &b.connections()[id];
+            assert_eq!((conn.get_src(), conn.get_dst()), 
+                (other.get_src(), other.get_dst()));
+        }
+    }
+
+    #[test]
+    fn initial_connections_keep_their_numbers() {
+        let config = NeatConfig {
+            num_inputs: 3,
+            num_outputs: 2,
+            num_hidden: 1,
+            ..config(true)
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let genome = Genome::new(0, &config, &mut rng);
+        let mut innovations = InnovationTracker::new(&config);
+        for conn in genome.connections().values() {
+            assert_eq!(innovations.get_connection_key(conn.get_src(), 
+                conn.get_dst()), conn.get_id());
+        }
+        let n = genome.connections().len();
+        // from the output 3 back to the hidden node 5
+        assert_eq!(innovations.get_connection_key(3, 5), n);
+        assert_eq!(innovations.get_split_node_key(0), 6);
+    }
+
+    #[test]
+    fn innovations_are_forgotten_between_generations() {
+        let config = config(true);
+        let mut innovations = InnovationTracker::new(&config);
+        let node = innovations.get_split_node_key(0);
+        let conn = innovations.get_connection_key(0, node);
+        assert_eq!(innovations.get_split_node_key(0), node);
+        innovations.next_generation(&config);
+        assert_ne!(innovations.get_split_node_key(0), node);
+        assert_ne!(innovations.get_connection_key(0, node), conn);
+
+        let config = self::config(false);
+        let mut innovations = InnovationTracker::new(&config);
+        let node = innovations.get_split_node_key(0);
+        let conn = innovations.get_connection_key(0, node);
+        innovations.next_generation(&config);
+        assert_eq!(innovations.get_split_node_key(0), node);
+        assert_eq!(innovations.get_connection_key(0, node), conn);
+    }
+
+    #[test]
+    fn registered_genomes_are_not_renumbered() {
+        let config = config(true);
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut genome = Genome::new(0, &config, &mut rng);
+        let mut other = InnovationTracker::new(&config);
+        for _ in 0..3 {
+            genome.mutate(&config, &mut other, &mut rng);
+            other.next_generation(&config);
+        }
+
+        let mut innovations = InnovationTracker::new(&config);
+        innovations.register(&genome);
+        let max_conn = genome.connections().keys().max().unwrap();
+        let max_node = genome.nodes().keys().max().unwrap();
+        assert!(innovations.get_connection_key(1, 0) > *max_conn);
+        assert!(innovations.get_split_node_key(0) > *max_node);
+    }
+}
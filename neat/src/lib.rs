@@ -1,6 +1,20 @@
-mod gene;
-mod config;
-mod activation;
-mod aggregation;
+pub mod gene;
+pub mod config;
+pub mod activation;
+pub mod aggregation;
 
-pub mod genome;
\ No newline at end of file
+pub mod checkpoint;
+pub mod distributed;
+pub mod dot;
+pub mod evaluation;
+pub mod genome;
+pub mod graphs;
+pub mod innovation;
+pub mod nn;
+pub mod parallel;
+pub mod species;
+pub mod population;
+pub mod reporting;
+pub mod reproduction;
+pub mod stagnation;
+pub mod statistics;
\ No newline at end of file
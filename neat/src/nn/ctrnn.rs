@@ -0,0 +1,134 @@
+use super::{NodeEval, StepValues};
+use crate::genome::Genome;
+
+/// Continuous-time recurrent neural network.
+///
+/// The state `y` of every node follows `tau * dy/dt = -y + z`, where
+/// `z = activation(bias + response * aggregation(inputs))` is computed from
+/// the weighted states of its inputs and `tau` is the node's time constant.
+/// The system is integrated with the forward Euler method.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone)]
+pub struct CTRNN {
+    input_nodes:  Vec<usize>,
+    output_nodes: Vec<usize>,
+    node_evals:   Vec<NodeEval>,
+    values:       StepValues,
+    time:         f64,
+}
+
+impl CTRNN {
+    /// Builds the network from the enabled connections of `genome`, using
+    /// each node's evolved time constant.
+    pub fn create(genome: &Genome) -> Self {
+        let node_evals = super::recurrent_node_evals(genome);
+        let values = StepValues::new(genome, &node_evals);
+        Self {
+            input_nodes: genome.input_keys().to_vec(),
+            output_nodes: genome.output_keys().to_vec(),
+            node_evals,
+            values,
+            time: 0.0,
+        }
+    }
+
+    /// Sets all node states and the simulated time back to zero.
+    pub fn reset(&mut self) {
+        self.values.reset();
+        self.time = 0.0;
+    }
+
+    /// Overrides the state of a single node, e.g. to set initial conditions.
+    pub fn set_node_value(&mut self, key: usize, value: f64) {
+        self.values.set(key, value);
+    }
+
+    /// Simulated time in seconds since creation or the last reset.
+    #[inline]
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
+    /// Integrates the network for `advance_time` seconds in steps of at most
+    /// `time_step` while holding `inputs` constant, and returns the output 
+    /// states in the order of the genome's output keys.
+    pub fn advance(&mut self, inputs: &[f64], advance_time: f64, 
+            time_step: f64) -> Vec<f64> {
+        assert_eq!(inputs.len(), self.input_nodes.len(), 
+            "expected {} inputs", self.input_nodes.len());
+        assert!(time_step > 0.0, "time step must be positive");
+
+        let final_time = self.time + advance_time;
+        while self.time < final_time {
+            let dt = time_step.min(final_time - self.time);
+
+            let (ivalues, ovalues) = self.values.step(&self.input_nodes, 
+                inputs);
+            for eval in self.node_evals.iter() {
+                let z = eval.activate(ivalues);
+                let key = eval.node.get_id();
+                let y = ivalues[&key];
+                ovalues.insert(key, 
+                    y + dt / eval.node.get_time_constant() * (z - y));
+            }
+
+            self.time += dt;
+        }
+
+        let ovalues = self.values.current();
+        self.output_nodes.iter().map(|k| ovalues[k]).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        activation::ActivationFunction,
+        config::{InitialConnection, NeatConfig},
+    };
+
+    fn relu_genome() -> Genome {
+        let config = NeatConfig {
+            num_inputs: 1,
+            num_outputs: 1,
+            initial_connection: InitialConnection::FullDirect,
+            weight_init_mean: 1.0,
+            weight_init_stdev: 0.0,
+            bias_init_mean: -1.0,
+            bias_init_stdev: 0.0,
+            time_constant_init_mean: 0.5,
+            activation_default: Some(ActivationFunction::Relu),
+            ..NeatConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        Genome::new(0, &config, &mut rng)
+    }
+
+    #[test]
+    fn states_converge_to_the_node_activation() {
+        let mut net = CTRNN::create(&relu_genome());
+        // z = relu(-1 + 2) = 1
+        let y = net.advance(&[2.0], 10.0, 0.01)[0];
+        assert!((y - 1.0).abs() < 1e-6, "{}", y);
+
+        // z = relu(-1 + 0.5) = 0
+        net.reset();
+        net.set_node_value(net.output_nodes[0], 0.5);
+        let y = net.advance(&[0.5], 10.0, 0.01)[0];
+        assert!(y.abs() < 1e-6, "{}", y);
+    }
+
+    #[test]
+    fn state_follows_the_time_constant() {
+        let mut net = CTRNN::create(&relu_genome());
+        // one time constant moves the state 1 - 1/e of the way towards z
+        let y = net.advance(&[2.0], 0.5, 0.0005)[0];
+        assert!((y - (1.0 - (-1.0f64).exp())).abs() < 1e-3, "{}", y);
+        assert!((net.get_time() - 0.5).abs() < 1e-9);
+    }
+}
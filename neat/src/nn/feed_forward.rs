@@ -0,0 +1,119 @@
+use hashbrown::HashMap;
+
+use super::NodeEval;
+use crate::{genome::Genome, graphs};
+
+/// Acyclic network evaluated layer by layer.
+#[derive(Debug, Clone)]
+pub struct FeedForwardNetwork {
+    input_nodes:  Vec<usize>,
+    output_nodes: Vec<usize>,
+    node_evals:   Vec<NodeEval>,
+    values:       HashMap<usize, f64>,
+}
+
+impl FeedForwardNetwork {
+    /// Orders the nodes of `genome` into layers along its enabled 
+    /// connections. Nodes that no output depends on are left out.
+    pub fn create(genome: &Genome) -> Self {
+        let connections: Vec<(usize, usize)> = genome.connections().values()
+            .filter(|c| c.is_enabled())
+            .map(|c| (c.get_src(), c.get_dst()))
+            .collect();
+        let layers = graphs::feed_forward_layers(genome.input_keys(), 
+            genome.output_keys(), &connections);
+
+        let mut node_evals = Vec::new();
+        for layer in layers {
+            for key in layer {
+                let mut inputs: Vec<(usize, f64)> = genome.connections()
+                    .values()
+                    .filter(|c| c.is_enabled() && c.get_dst() == key)
+                    .map(|c| (c.get_src(), c.get_weight()))
+                    .collect();
+                inputs.sort_by_key(|&(src, _)| src);
+
+                node_evals.push(NodeEval {
+                    node: genome.nodes()[&key].clone(),
+                    inputs,
+                });
+            }
+        }
+
+        let values = genome.input_keys().iter()
+            .chain(genome.output_keys())
+            .map(|&k| (k, 0.0))
+            .collect();
+
+        Self {
+            input_nodes: genome.input_keys().to_vec(),
+            output_nodes: genome.output_keys().to_vec(),
+            node_evals,
+            values,
+        }
+    }
+
+    /// Feeds `inputs` through the network and returns the output values in
+    /// the order of the genome's output keys. Nodes without incoming
+    /// connections are evaluated on an empty input, outputs caught in a cycle
+    /// evaluate to 0.
+    pub fn activate(&mut self, inputs: &[f64]) -> Vec<f64> {
+        assert_eq!(inputs.len(), self.input_nodes.len(), 
+            "expected {} inputs", self.input_nodes.len());
+
+        for (k, v) in self.input_nodes.iter().zip(inputs) {
+            self.values.insert(*k, *v);
+        }
+
+        for eval in self.node_evals.iter() {
+            let value = eval.activate(&self.values);
+            self.values.insert(eval.node.get_id(), value);
+        }
+
+        self.output_nodes.iter().map(|k| self.values[k]).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        activation::ActivationFunction,
+        config::{InitialConnection, NeatConfig},
+        innovation::InnovationTracker,
+    };
+
+    #[test]
+    fn nodes_apply_bias_and_response_before_the_activation() {
+        let config = NeatConfig {
+            num_inputs: 2,
+            num_outputs: 1,
+            initial_connection: InitialConnection::Unconnected,
+            bias_init_mean: 0.2,
+            bias_init_stdev: 0.0,
+            response_init_mean: 3.0,
+            response_init_stdev: 0.0,
+            activation_default: Some(ActivationFunction::Sigmoid),
+            ..NeatConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&config);
+        let mut genome = Genome::new(0, &config, &mut rng);
+        let [a, b] = [genome.input_keys()[0], genome.input_keys()[1]];
+        let out = genome.output_keys()[0];
+        genome.add_connection(&mut innovations, a, out, 0.5, true);
+        genome.add_connection(&mut innovations, b, out, -0.25, true);
+
+        let mut net = FeedForwardNetwork::create(&genome);
+        // sigmoid(0.2 + 3 * (0.5 - 0.5))
+        let expected = ActivationFunction::Sigmoid.activate(0.2);
+        assert_eq!(net.activate(&[1.0, 2.0]), [expected]);
+        // sigmoid(0.2 + 3 * 0.5)
+        let expected = ActivationFunction::Sigmoid.activate(1.7);
+        assert_eq!(net.activate(&[1.0, 0.0]), [expected]);
+    }
+}
@@ -0,0 +1,241 @@
+use hashbrown::HashMap;
+
+use crate::genome::Genome;
+
+/// Membrane potential at which a neuron fires, in mV.
+const FIRING_THRESHOLD: f64 = 30.0;
+
+#[derive(Debug, Clone)]
+struct Neuron {
+    key:  usize,
+    bias: f64,
+
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+
+    inputs: Vec<(usize, f64)>,
+
+    v:       f64,
+    u:       f64,
+    fired:   bool,
+    current: f64,
+}
+
+impl Neuron {
+    fn new(key: usize, bias: f64, (a, b, c, d): (f64, f64, f64, f64),
+            inputs: Vec<(usize, f64)>) -> Self {
+        Self {
+            key,
+            bias,
+            a, b, c, d,
+            inputs,
+            v: c,
+            u: b * c,
+            fired: false,
+            current: bias,
+        }
+    }
+
+    fn advance(&mut self, dt: f64) {
+        // two half steps for v for numerical stability
+        for _ in 0..2 {
+            self.v += 0.5 * dt * (0.04 * self.v.powi(2) + 5.0 * self.v + 
+                140.0 - self.u + self.current);
+        }
+        self.u += dt * self.a * (self.b * self.v - self.u);
+
+        if !self.v.is_finite() || !self.u.is_finite() {
+            self.v = self.c;
+            self.u = self.b * self.v;
+        }
+
+        self.fired = self.v > FIRING_THRESHOLD;
+        if self.fired {
+            self.v = self.c;
+            self.u += self.d;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.v = self.c;
+        self.u = self.b * self.v;
+        self.fired = false;
+        self.current = self.bias;
+    }
+}
+
+/// Spiking network of Izhikevich neurons.
+///
+/// Every non-input node is a neuron with the `a`, `b`, `c` and `d`
+/// parameters stored on its gene. Each step, a neuron receives its bias plus
+/// the weighted input values and the weighted spikes of the previous step
+/// as input current.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone)]
+pub struct IZNN {
+    input_nodes:  Vec<usize>,
+    output_nodes: Vec<usize>,
+    neurons:      Vec<Neuron>,
+    index:        HashMap<usize, usize>,
+    input_values: HashMap<usize, f64>,
+}
+
+impl IZNN {
+    /// Builds the network from the enabled connections of `genome`. Nodes 
+    /// that do not lie on a path to an output are pruned.
+    pub fn create(genome: &Genome) -> Self {
+        let mut node_inputs: HashMap<usize, Vec<(usize, f64)>> = 
+            super::required_node_inputs(genome).into_iter().collect();
+        for key in genome.output_keys() {
+            node_inputs.entry(*key).or_default();
+        }
+
+        let mut keys: Vec<usize> = node_inputs.keys().copied().collect();
+        keys.sort();
+        let neurons: Vec<Neuron> = keys.into_iter().map(|key| {
+            let node = &genome.nodes()[&key];
+            Neuron::new(key, node.get_bias(), node.get_izhikevich_params(),
+                node_inputs.remove(&key).unwrap())
+        }).collect();
+        let index = neurons.iter().enumerate()
+            .map(|(i, n)| (n.key, i))
+            .collect();
+
+        Self {
+            input_nodes: genome.input_keys().to_vec(),
+            output_nodes: genome.output_keys().to_vec(),
+            neurons,
+            index,
+            input_values: genome.input_keys().iter()
+                .map(|&k| (k, 0.0))
+                .collect(),
+        }
+    }
+
+    /// Sets the values fed into the input nodes by the following steps.
+    pub fn set_inputs(&mut self, inputs: &[f64]) {
+        assert_eq!(inputs.len(), self.input_nodes.len(), 
+            "expected {} inputs", self.input_nodes.len());
+        for (k, v) in self.input_nodes.iter().zip(inputs) {
+            self.input_values.insert(*k, *v);
+        }
+    }
+
+    /// Puts every neuron back into its resting state.
+    pub fn reset(&mut self) {
+        for n in self.neurons.iter_mut() {
+            n.reset();
+        }
+    }
+
+    /// Advances the network by `dt` milliseconds and reports which outputs
+    /// fired, in the order of the genome's output keys.
+    pub fn advance(&mut self, dt: f64) -> Vec<bool> {
+        let currents: Vec<f64> = self.neurons.iter().map(|n| {
+            n.bias + n.inputs.iter().map(|(src, w)| {
+                let value = match self.index.get(src) {
+                    Some(&i) => if self.neurons[i].fired { 1.0 } else { 0.0 },
+                    None => self.input_values.get(src).copied()
+                        .unwrap_or(0.0),
+                };
+                value * w
+            }).sum::<f64>()
+        }).collect();
+
+        for (n, current) in self.neurons.iter_mut().zip(currents) {
+            n.current = current;
+            n.advance(dt);
+        }
+
+        self.output_nodes.iter()
+            .map(|k| self.neurons[self.index[k]].fired)
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        config::{InitialConnection, NeatConfig},
+        innovation::InnovationTracker,
+    };
+
+    /// Genome of regular spiking neurons without bias, connected by `links`.
+    /// Node 0 is the input, 1 the output and 2 a hidden node.
+    fn genome(links: &[(usize, usize, f64)]) -> Genome {
+        let config = NeatConfig {
+            num_inputs: 1,
+            num_outputs: 1,
+            num_hidden: 1,
+            initial_connection: InitialConnection::Unconnected,
+            bias_init_mean: 0.0,
+            bias_init_stdev: 0.0,
+            ..NeatConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&config);
+        let mut genome = Genome::new(0, &config, &mut rng);
+        for &(src, dst, weight) in links {
+            genome.add_connection(&mut innovations, src, dst, weight, true);
+        }
+        genome
+    }
+
+    fn num_spikes(net: &mut IZNN, steps: usize) -> usize {
+        (0..steps).filter(|_| net.advance(1.0)[0]).count()
+    }
+
+    #[test]
+    fn constant_current_above_threshold_fires() {
+        let mut net = IZNN::create(&genome(&[(0, 1, 1.0)]));
+        net.set_inputs(&[10.0]);
+        assert!(num_spikes(&mut net, 200) > 1);
+
+        net.reset();
+        net.set_inputs(&[0.0]);
+        assert_eq!(num_spikes(&mut net, 200), 0);
+    }
+
+    #[test]
+    fn reset_restores_the_resting_state() {
+        let mut net = IZNN::create(&genome(&[(0, 1, 1.0)]));
+        net.set_inputs(&[10.0]);
+        num_spikes(&mut net, 50);
+        let n = &net.neurons[net.index[&1]];
+        assert_ne!(n.v, n.c);
+
+        net.reset();
+        let n = &net.neurons[net.index[&1]];
+        assert_eq!(n.v, n.c);
+        assert_eq!(n.u, n.b * n.v);
+        assert!(!n.fired);
+    }
+
+    #[test]
+    fn spikes_of_hidden_neurons_reach_their_targets() {
+        let mut net = IZNN::create(&genome(&[(0, 2, 10.0), (2, 1, 200.0)]));
+        net.set_inputs(&[1.0]);
+        let hidden = net.index[&2];
+
+        let mut first_hidden_spike = None;
+        let mut first_output_spike = None;
+        for step in 0..200 {
+            let output = net.advance(1.0)[0];
+            if net.neurons[hidden].fired {
+                first_hidden_spike.get_or_insert(step);
+            }
+            if output {
+                first_output_spike.get_or_insert(step);
+            }
+        }
+        // the output has no other source of current
+        assert!(first_output_spike.unwrap() > first_hidden_spike.unwrap());
+    }
+}
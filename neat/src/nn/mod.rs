@@ -0,0 +1,145 @@
+//! Phenotypes that can be built from a [`Genome`].
+
+mod ctrnn;
+mod feed_forward;
+mod iznn;
+mod recurrent;
+
+pub use ctrnn::CTRNN;
+pub use feed_forward::FeedForwardNetwork;
+pub use iznn::IZNN;
+pub use recurrent::RecurrentNetwork;
+
+use hashbrown::HashMap;
+
+use crate::{gene::NodeGene, genome::Genome, graphs};
+
+/// A node together with the `(src, weight)` links whose values it sums.
+#[derive(Debug, Clone)]
+struct NodeEval {
+    node:   NodeGene,
+    inputs: Vec<(usize, f64)>,
+}
+
+impl NodeEval {
+    /// Value of the node, reading the values of its sources from `values`.
+    fn activate(&self, values: &HashMap<usize, f64>) -> f64 {
+        let vals: Vec<f64> = self.inputs.iter()
+            .map(|(src, w)| values[src] * w)
+            .collect();
+        self.node.activate(&vals)
+    }
+}
+
+/// Incoming `(src, weight)` links of every node that is required to compute
+/// the outputs, following enabled connections only. Cycles are allowed.
+fn required_node_inputs(genome: &Genome) -> Vec<(usize, Vec<(usize, f64)>)> {
+    let connections: Vec<(usize, usize)> = genome.connections().values()
+        .filter(|c| c.is_enabled())
+        .map(|c| (c.get_src(), c.get_dst()))
+        .collect();
+    let required = graphs::required_for_output(genome.input_keys(),
+        genome.output_keys(), &connections);
+
+    let mut node_inputs: HashMap<usize, Vec<(usize, f64)>> = HashMap::new();
+    for conn in genome.connections().values() {
+        if !conn.is_enabled() || !required.contains(&conn.get_dst()) {
+            continue;
+        }
+        node_inputs.entry(conn.get_dst()).or_default()
+            .push((conn.get_src(), conn.get_weight()));
+    }
+
+    let mut ret: Vec<(usize, Vec<(usize, f64)>)> = node_inputs.into_iter()
+        .map(|(key, mut inputs)| {
+            inputs.sort_by_key(|&(src, _)| src);
+            (key, inputs)
+        })
+        .collect();
+    ret.sort_by_key(|&(key, _)| key);
+    ret
+}
+
+/// Every node required to compute the outputs, by key, for networks that
+/// evaluate all nodes at once from the values of the previous step. Outputs 
+/// without incoming connections are included, so that they evaluate to 
+/// their activated bias as in a [`FeedForwardNetwork`].
+fn recurrent_node_evals(genome: &Genome) -> Vec<NodeEval> {
+    let mut node_inputs = required_node_inputs(genome);
+    for &key in genome.output_keys() {
+        if !node_inputs.iter().any(|&(k, _)| k == key) {
+            node_inputs.push((key, Vec::new()));
+        }
+    }
+    node_inputs.sort_by_key(|&(key, _)| key);
+
+    node_inputs.into_iter()
+        .map(|(key, inputs)| NodeEval { 
+            node: genome.nodes()[&key].clone(), 
+            inputs,
+        })
+        .collect()
+}
+
+/// Node values of the previous and of the current step, for networks that
+/// evaluate all nodes at once.
+#[derive(Debug, Clone)]
+struct StepValues {
+    values: [HashMap<usize, f64>; 2],
+    active: usize,
+}
+
+impl StepValues {
+    /// Zero for the inputs and outputs of `genome` and every node of
+    /// `node_evals` or read by them.
+    fn new(genome: &Genome, node_evals: &[NodeEval]) -> Self {
+        let mut values = HashMap::new();
+        for key in genome.input_keys().iter().chain(genome.output_keys()) {
+            values.insert(*key, 0.0);
+        }
+        for eval in node_evals.iter() {
+            values.insert(eval.node.get_id(), 0.0);
+            for (src, _) in eval.inputs.iter() {
+                values.insert(*src, 0.0);
+            }
+        }
+        Self {
+            values: [values.clone(), values],
+            active: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        for values in self.values.iter_mut() {
+            for v in values.values_mut() {
+                *v = 0.0;
+            }
+        }
+        self.active = 0;
+    }
+
+    /// Sets the value of `key` in both steps.
+    fn set(&mut self, key: usize, value: f64) {
+        for values in self.values.iter_mut() {
+            values.insert(key, value);
+        }
+    }
+
+    /// Starts a new step with `inputs` written to the nodes `input_nodes`,
+    /// and returns the values of the previous step and those to be written.
+    fn step(&mut self, input_nodes: &[usize], inputs: &[f64]) 
+            -> (&HashMap<usize, f64>, &mut HashMap<usize, f64>) {
+        for (k, v) in input_nodes.iter().zip(inputs) {
+            self.set(*k, *v);
+        }
+        self.active = 1 - self.active;
+        let [a, b] = &mut self.values;
+        if self.active == 0 { (a, b) } else { (b, a) }
+    }
+
+    /// Values written by the last step.
+    fn current(&self) -> &HashMap<usize, f64> {
+        &self.values[1 - self.active]
+    }
+}
+
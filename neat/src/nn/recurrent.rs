@@ -0,0 +1,135 @@
+use super::{NodeEval, StepValues};
+use crate::genome::Genome;
+
+/// Network that may contain cycles and self-connections.
+///
+/// Every node is evaluated once per tick from the values of the previous
+/// tick, so the node values persist between calls to
+/// [`RecurrentNetwork::activate`] until [`RecurrentNetwork::reset`] is called.
+#[derive(Debug, Clone)]
+pub struct RecurrentNetwork {
+    input_nodes:  Vec<usize>,
+    output_nodes: Vec<usize>,
+    node_evals:   Vec<NodeEval>,
+    ticks:        usize,
+    values:       StepValues,
+}
+
+impl RecurrentNetwork {
+    /// Builds the network from the enabled connections of `genome`, cycles
+    /// included. Outputs without incoming connections evaluate to their
+    /// activated bias.
+    pub fn create(genome: &Genome) -> Self {
+        let node_evals = super::recurrent_node_evals(genome);
+        let values = StepValues::new(genome, &node_evals);
+        Self {
+            input_nodes: genome.input_keys().to_vec(),
+            output_nodes: genome.output_keys().to_vec(),
+            node_evals,
+            ticks: 1,
+            values,
+        }
+    }
+
+    /// Sets how many internal ticks every call to 
+    /// [`RecurrentNetwork::activate`] runs. The inputs are held constant
+    /// during these ticks.
+    pub fn with_ticks(mut self, ticks: usize) -> Self {
+        assert!(ticks > 0, "at least one tick is required");
+        self.ticks = ticks;
+        self
+    }
+
+    /// Sets all node values back to zero.
+    pub fn reset(&mut self) {
+        self.values.reset();
+    }
+
+    /// Advances the network by the configured number of ticks and returns 
+    /// the output values in the order of the genome's output keys.
+    pub fn activate(&mut self, inputs: &[f64]) -> Vec<f64> {
+        assert_eq!(inputs.len(), self.input_nodes.len(), 
+            "expected {} inputs", self.input_nodes.len());
+
+        for _ in 0..self.ticks {
+            let (ivalues, ovalues) = self.values.step(&self.input_nodes, 
+                inputs);
+            for eval in self.node_evals.iter() {
+                ovalues.insert(eval.node.get_id(), eval.activate(ivalues));
+            }
+        }
+
+        let ovalues = self.values.current();
+        self.output_nodes.iter().map(|k| ovalues[k]).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        activation::ActivationFunction,
+        config::{InitialConnection, NeatConfig},
+        innovation::InnovationTracker,
+        nn::FeedForwardNetwork,
+    };
+
+    /// Identity nodes with a bias of 0.5 where the input feeds the first 
+    /// output, which also feeds back into itself. The second output is not
+    /// connected.
+    fn accumulator() -> Genome {
+        let config = NeatConfig {
+            num_inputs: 1,
+            num_outputs: 2,
+            initial_connection: InitialConnection::Unconnected,
+            feed_forward: false,
+            bias_init_mean: 0.5,
+            bias_init_stdev: 0.0,
+            activation_default: Some(ActivationFunction::Identity),
+            ..NeatConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&config);
+        let mut genome = Genome::new(0, &config, &mut rng);
+        genome.add_connection(&mut innovations, 0, 1, 1.0, true);
+        genome.add_connection(&mut innovations, 1, 1, 1.0, true);
+        genome
+    }
+
+    #[test]
+    fn self_loops_carry_state_between_activations() {
+        let mut net = RecurrentNetwork::create(&accumulator());
+        assert_eq!(net.activate(&[1.0])[0], 1.5);
+        assert_eq!(net.activate(&[0.0])[0], 2.0);
+        assert_eq!(net.activate(&[0.0])[0], 2.5);
+    }
+
+    #[test]
+    fn every_tick_advances_the_state() {
+        let mut net = RecurrentNetwork::create(&accumulator()).with_ticks(3);
+        // 1.5, then 3.0, then 4.5
+        assert_eq!(net.activate(&[1.0])[0], 4.5);
+    }
+
+    #[test]
+    fn reset_clears_the_state() {
+        let mut net = RecurrentNetwork::create(&accumulator());
+        net.activate(&[1.0]);
+        net.activate(&[1.0]);
+        net.reset();
+        assert_eq!(net.activate(&[0.0])[0], 0.5);
+    }
+
+    #[test]
+    fn unconnected_outputs_evaluate_to_their_bias() {
+        let genome = accumulator();
+        let mut net = RecurrentNetwork::create(&genome);
+        let mut ff = FeedForwardNetwork::create(&genome);
+        assert_eq!(net.activate(&[1.0])[1], 0.5);
+        assert_eq!(ff.activate(&[1.0])[1], 0.5);
+    }
+}
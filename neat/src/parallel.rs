@@ -0,0 +1,252 @@
+//! Evaluates the fitness of a population on several threads.
+
+use std::{
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc::{self, Receiver, RecvTimeoutError, Sender}, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use hashbrown::HashMap;
+
+use crate::{
+    config::NeatConfig,
+    evaluation::{EvaluationFailure, OnFailure},
+    genome::Genome,
+};
+
+// genomes are handed to worker threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Genome>();
+};
+
+enum Message {
+    Started(usize, Instant),
+    // `None` if the evaluation panicked
+    Finished(usize, Option<f64>),
+}
+
+type Jobs = Arc<Mutex<Receiver<(usize, Genome)>>>;
+
+/// Runs a fitness function on a pool of worker threads, one genome at a 
+/// time per thread.
+///
+/// A genome whose evaluation panics, or runs longer than the timeout, is
+/// dealt with as set by [`ParallelEvaluator::on_failure_mut`].
+/// A thread cannot be interrupted, so an evaluation that timed out keeps
+/// running in the background until it returns, and a fresh worker takes 
+/// its place in the meantime.
+///
+/// ```ignore
+/// let evaluator = ParallelEvaluator::new(8, None, |g: &Genome| eval(g));
+/// population.run(|genomes, config| evaluator.evaluate(genomes, config),
+///     Some(100));
+/// ```
+pub struct ParallelEvaluator<F> {
+    num_workers: usize,
+    timeout:     Option<Duration>,
+    on_failure:  OnFailure,
+    eval_fn:     Arc<F>,
+}
+
+impl<F> ParallelEvaluator<F>
+        where F: Fn(&Genome) -> f64 + Send + Sync + 'static {
+    /// Uses one worker per available CPU if `num_workers` is 0.
+    pub fn new(num_workers: usize, timeout: Option<Duration>, eval_fn: F) 
+            -> Self {
+        let num_workers = if num_workers == 0 {
+            thread::available_parallelism().map_or(1, NonZeroUsize::get)
+        } else {
+            num_workers
+        };
+        Self {
+            num_workers,
+            timeout,
+            on_failure: OnFailure::default(),
+            eval_fn: Arc::new(eval_fn),
+        }
+    }
+
+    #[inline]
+    pub fn num_workers(&self) -> usize {
+        self.num_workers
+    }
+
+    #[inline]
+    pub fn on_failure(&self) -> &OnFailure {
+        &self.on_failure
+    }
+
+    /// Failure fitness and handler for genomes whose evaluation panicked or
+    /// timed out.
+    #[inline]
+    pub fn on_failure_mut(&mut self) -> &mut OnFailure {
+        &mut self.on_failure
+    }
+
+    fn spawn_worker(&self, jobs: &Jobs, results: &Sender<Message>) {
+        let eval_fn = Arc::clone(&self.eval_fn);
+        let jobs = Arc::clone(jobs);
+        let results = results.clone();
+        thread::Builder::new()
+            .name("neat-eval".to_string())
+            .spawn(move || loop {
+                let job = jobs.lock().unwrap().recv();
+                let Ok((i, genome)) = job else { break };
+
+                if results.send(Message::Started(i, Instant::now())).is_err() {
+                    break;
+                }
+                let fitness = panic::catch_unwind(
+                    AssertUnwindSafe(|| eval_fn(&genome))).ok();
+                // the receiver is gone if this evaluation timed out and was
+                // the last one
+                if results.send(Message::Finished(i, fitness)).is_err() {
+                    break;
+                }
+            })
+            .expect("failed to spawn evaluation thread");
+    }
+
+    /// Sets the fitness of every genome, blocking until the last evaluation
+    /// finished or timed out.
+    pub fn evaluate(&self, genomes: &mut [&mut Genome], _config: &NeatConfig) {
+        if genomes.is_empty() {
+            return;
+        }
+
+        let (job_tx, job_rx) = mpsc::channel();
+        for (i, g) in genomes.iter().enumerate() {
+            job_tx.send((i, (**g).clone())).unwrap();
+        }
+        drop(job_tx);
+        let jobs: Jobs = Arc::new(Mutex::new(job_rx));
+
+        let (result_tx, result_rx) = mpsc::channel();
+        for _ in 0..self.num_workers.min(genomes.len()) {
+            self.spawn_worker(&jobs, &result_tx);
+        }
+
+        // evaluations in progress, by the time they started
+        let mut running: HashMap<usize, Instant> = HashMap::new();
+        let mut remaining = genomes.len();
+        while remaining > 0 {
+            let deadline = self.timeout.and_then(|t| running.values()
+                .min()
+                .map(|&start| start + t));
+            let msg = match deadline {
+                Some(d) => result_rx.recv_timeout(
+                    d.saturating_duration_since(Instant::now())),
+                // `result_tx` is still alive, so `recv` cannot fail
+                None => Ok(result_rx.recv().unwrap()),
+            };
+
+            match msg {
+                Ok(Message::Started(i, start)) => {
+                    running.insert(i, start);
+                }
+                Ok(Message::Finished(i, fitness)) => {
+                    // results of evaluations that timed out are dropped
+                    if running.remove(&i).is_none() {
+                        continue;
+                    }
+                    match fitness {
+                        Some(fitness) => genomes[i].set_fitness(fitness),
+                        None => self.on_failure.fail(genomes[i], 
+                            EvaluationFailure::Panicked),
+                    }
+                    remaining -= 1;
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    let timeout = self.timeout.unwrap();
+                    let now = Instant::now();
+                    let mut expired: Vec<usize> = running.iter()
+                        .filter(|(_, &start)| now >= start + timeout)
+                        .map(|(&i, _)| i)
+                        .collect();
+                    expired.sort_unstable();
+                    for i in expired {
+                        running.remove(&i);
+                        self.on_failure.fail(genomes[i], 
+                            EvaluationFailure::TimedOut);
+                        remaining -= 1;
+                        self.spawn_worker(&jobs, &result_tx);
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => unreachable!(),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::testing::{genomes, record_failures};
+
+    #[test]
+    fn every_genome_is_evaluated() {
+        let evaluator = ParallelEvaluator::new(3, None, 
+            |g: &Genome| g.get_id() as f64 * 2.0);
+        let mut genomes = genomes(20);
+        let mut refs: Vec<&mut Genome> = genomes.iter_mut().collect();
+        evaluator.evaluate(&mut refs, &NeatConfig::default());
+        for g in genomes.iter() {
+            assert_eq!(g.get_fitness(), g.get_id() as f64 * 2.0);
+        }
+    }
+
+    #[test]
+    fn panics_get_the_failure_fitness() {
+        let mut evaluator = ParallelEvaluator::new(2, None, |g: &Genome| {
+            assert!(!g.get_id().is_multiple_of(3), "cannot evaluate {}", 
+                g.get_id());
+            1.0
+        });
+        evaluator.on_failure_mut().set_fitness(-1.0);
+        let failures = record_failures(evaluator.on_failure_mut());
+
+        let mut genomes = genomes(7);
+        let mut refs: Vec<&mut Genome> = genomes.iter_mut().collect();
+        evaluator.evaluate(&mut refs, &NeatConfig::default());
+        let fitnesses: Vec<f64> = genomes.iter()
+            .map(|g| g.get_fitness())
+            .collect();
+        assert_eq!(fitnesses, [-1.0, 1.0, 1.0, -1.0, 1.0, 1.0, -1.0]);
+
+        let mut failures = failures.lock().unwrap().clone();
+        failures.sort_by_key(|&(id, _)| id);
+        assert_eq!(failures, [
+            (0, EvaluationFailure::Panicked),
+            (3, EvaluationFailure::Panicked),
+            (6, EvaluationFailure::Panicked),
+        ]);
+    }
+
+    #[test]
+    fn slow_evaluations_time_out() {
+        let mut evaluator = ParallelEvaluator::new(2, 
+            Some(Duration::from_millis(100)), |g: &Genome| {
+                if g.get_id() == 1 {
+                    thread::sleep(Duration::from_secs(2));
+                }
+                1.0
+            });
+        let failures = record_failures(evaluator.on_failure_mut());
+
+        let start = Instant::now();
+        let mut genomes = genomes(6);
+        let mut refs: Vec<&mut Genome> = genomes.iter_mut().collect();
+        evaluator.evaluate(&mut refs, &NeatConfig::default());
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let fitnesses: Vec<f64> = genomes.iter()
+            .map(|g| g.get_fitness())
+            .collect();
+        assert_eq!(fitnesses, [1.0, 0.0, 1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(*failures.lock().unwrap(), 
+            [(1, EvaluationFailure::TimedOut)]);
+    }
+}
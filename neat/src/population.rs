@@ -0,0 +1,340 @@
+use std::path::Path;
+
+use hashbrown::HashMap;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    checkpoint::{self, CheckpointError, Checkpointer},
+    config::NeatConfig,
+    genome::Genome,
+    innovation::InnovationTracker,
+    reporting::{Reporter, ReporterSet},
+    reproduction::Reproduction,
+    species::SpeciesSet,
+    stagnation::Stagnation,
+};
+
+/// A population of genomes evolving under a single [`NeatConfig`].
+///
+/// The whole state of a population serializes with serde, which is what
+/// checkpoints are made of. Reporters and the checkpointer are not part of
+/// that state and are neither cloned nor saved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Population {
+    config: NeatConfig,
+
+    genomes:      HashMap<usize, Genome>,
+    species:      SpeciesSet,
+    reproduction: Reproduction,
+    innovations:  InnovationTracker,
+
+    generation:  usize,
+    best_genome: Option<Genome>,
+
+    rng: ChaCha8Rng,
+
+    #[serde(skip)]
+    reporters:    ReporterSet,
+    #[serde(skip)]
+    checkpointer: Option<Checkpointer>,
+}
+
+impl Population {
+    /// Creates `config.pop_size` fresh genomes and sorts them into species.
+    pub fn new(config: NeatConfig) -> Self {
+        let mut rng = Self::create_rng(&config);
+        let genomes = Reproduction::new().create_new(&config, config.pop_size,
+            &mut rng);
+        Self::from_parts(config, genomes, rng)
+    }
+
+    /// Starts from the given genomes instead of fresh ones, e.g. to seed the
+    /// population with hand-made structure. Genomes created later on, after
+    /// an extinction, are fresh again.
+    pub fn from_genomes(config: NeatConfig, genomes: HashMap<usize, Genome>)
+            -> Self {
+        let rng = Self::create_rng(&config);
+        Self::from_parts(config, genomes, rng)
+    }
+
+    fn create_rng(config: &NeatConfig) -> ChaCha8Rng {
+        match config.seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::from_entropy(),
+        }
+    }
+
+    fn from_parts(config: NeatConfig, genomes: HashMap<usize, Genome>, 
+            rng: ChaCha8Rng) -> Self {
+        let mut reproduction = Reproduction::new();
+        let next_id = genomes.keys().max().map_or(0, |k| k + 1);
+        reproduction.set_next_genome_id(next_id);
+        let mut species = SpeciesSet::new(&config);
+        species.speciate(&config, &genomes, 0);
+
+        let mut ids: Vec<usize> = genomes.keys().copied().collect();
+        ids.sort_unstable();
+        let mut innovations = InnovationTracker::new(&config);
+        for id in ids {
+            innovations.register(&genomes[&id]);
+        }
+
+        Self {
+            config,
+            genomes,
+            species,
+            reproduction,
+            innovations,
+            generation: 0,
+            best_genome: None,
+            rng,
+            reporters: ReporterSet::new(),
+            checkpointer: None,
+        }
+    }
+
+    /// Attaches a reporter that is notified about the progress of
+    /// [`Population::run`]. Any number of reporters can be attached.
+    pub fn add_reporter<R: Reporter + Send + 'static>(&mut self, reporter: R) {
+        self.reporters.add(reporter);
+    }
+
+    /// Saves the population with `checkpointer` while running. Saved and
+    /// failed checkpoints are passed on to the reporters. Checkpointers
+    /// are not part of a checkpoint and have to be set again after
+    /// [`Population::restore`].
+    pub fn set_checkpointer(&mut self, checkpointer: Checkpointer) {
+        self.checkpointer = Some(checkpointer);
+    }
+
+    /// Writes the full state of the population to `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CheckpointError> {
+        checkpoint::save(self, path.as_ref())
+    }
+
+    /// Loads a population saved by [`Population::save`] or a
+    /// [`Checkpointer`]. Running it continues exactly where the saved run
+    /// would have continued.
+    pub fn restore<P: AsRef<Path>>(path: P) -> Result<Self, CheckpointError> {
+        checkpoint::restore(path.as_ref())
+    }
+
+    #[inline]
+    pub fn config(&self) -> &NeatConfig {
+        &self.config
+    }
+
+    #[inline]
+    pub fn genomes(&self) -> &HashMap<usize, Genome> {
+        &self.genomes
+    }
+
+    #[inline]
+    pub fn species(&self) -> &SpeciesSet {
+        &self.species
+    }
+
+    #[inline]
+    pub fn get_generation(&self) -> usize {
+        self.generation
+    }
+
+    /// The fittest genome seen so far, across all generations.
+    #[inline]
+    pub fn best_genome(&self) -> Option<&Genome> {
+        self.best_genome.as_ref()
+    }
+
+    /// Runs the evolutionary loop for at most `generations` generations, or
+    /// until the population reaches `config.fitness_threshold` as measured
+    /// by `config.fitness_criterion` if no limit is given. The threshold is
+    /// ignored if `config.no_fitness_termination` is set.
+    ///
+    /// `fitness_fn` is called once per generation and has to set the fitness
+    /// of every genome it is handed. Species that stagnate are removed; if
+    /// all of them do, the population is either replaced by fresh genomes or
+    /// the run stops, depending on `config.reset_on_extinction`. Returns the
+    /// best genome found.
+    pub fn run<F>(&mut self, mut fitness_fn: F, generations: Option<usize>)
+            -> Option<Genome>
+            where F: FnMut(&mut [&mut Genome], &NeatConfig) {
+        assert!(generations.is_some() || !self.config.no_fitness_termination,
+            "a run without fitness termination needs a generation limit");
+        let mut k = 0;
+        while generations.is_none_or(|n| k < n) {
+            k += 1;
+            self.reporters.start_generation(self.generation);
+
+            // handed out in order of their ids, so that seeded runs do not
+            // depend on the iteration order of the map
+            let mut genomes: Vec<&mut Genome> = self.genomes.values_mut()
+                .collect();
+            genomes.sort_unstable_by_key(|g| g.get_id());
+            fitness_fn(&mut genomes, &self.config);
+
+            let best = self.genomes.values()
+                .max_by(|a, b| a.get_fitness().total_cmp(&b.get_fitness())
+                    .then(b.get_id().cmp(&a.get_id())))
+                .unwrap();
+            if self.best_genome.as_ref()
+                    .is_none_or(|b| best.get_fitness() > b.get_fitness()) {
+                self.best_genome = Some(best.clone());
+            }
+            self.reporters.post_evaluate(&self.config, &self.genomes,
+                &self.species, best);
+
+            let mut ids: Vec<usize> = self.genomes.keys().copied().collect();
+            ids.sort_unstable();
+            let fitnesses: Vec<f64> = ids.iter()
+                .map(|id| self.genomes[id].get_fitness())
+                .collect();
+            let criterion = self.config.fitness_criterion.apply(&fitnesses);
+            if !self.config.no_fitness_termination && 
+                    criterion >= self.config.fitness_threshold {
+                self.reporters.found_solution(&self.config, self.generation,
+                    best);
+                break;
+            }
+
+            let stagnation = Stagnation::update(&self.config, 
+                &mut self.species, &self.genomes, self.generation);
+            for (key, is_stagnant) in stagnation {
+                if is_stagnant {
+                    if let Some(species) = self.species.remove(key) {
+                        self.reporters.species_stagnant(key, &species);
+                    }
+                }
+            }
+
+            if self.species.is_empty() {
+                // every species stagnated at once
+                self.reporters.complete_extinction();
+                if !self.config.reset_on_extinction {
+                    break;
+                }
+                self.genomes = self.reproduction.create_new(&self.config,
+                    self.config.pop_size, &mut self.rng);
+            } else {
+                self.genomes = self.reproduction.reproduce(&self.config,
+                    &mut self.species, &self.genomes, &mut self.innovations,
+                    &mut self.rng);
+            }
+            self.reporters.post_reproduction(&self.config, &self.genomes,
+                &self.species);
+            self.innovations.next_generation(&self.config);
+            self.species.speciate(&self.config, &self.genomes,
+                self.generation + 1);
+            self.reporters.end_generation(&self.config, self.generation,
+                &self.genomes, &self.species);
+            self.generation += 1;
+
+            if let Some(mut checkpointer) = self.checkpointer.take() {
+                match checkpointer.end_generation(self) {
+                    Ok(Some(path)) => self.reporters.checkpoint_saved(
+                        self.generation, &path),
+                    Ok(None) => {}
+                    Err(e) => self.reporters.checkpoint_failed(
+                        self.generation, &e),
+                }
+                self.checkpointer = Some(checkpointer);
+            }
+        }
+
+        self.best_genome.clone()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::FitnessFunction, nn::FeedForwardNetwork};
+
+    const XOR: [([f64; 2], f64); 4] = [
+        ([0.0, 0.0], 0.0),
+        ([0.0, 1.0], 1.0),
+        ([1.0, 0.0], 1.0),
+        ([1.0, 1.0], 0.0),
+    ];
+
+    fn xor_config(seed: u64) -> NeatConfig {
+        NeatConfig {
+            seed: Some(seed),
+            num_inputs: 2,
+            num_outputs: 1,
+            ..NeatConfig::default()
+        }
+    }
+
+    fn xor_fitness(genomes: &mut [&mut Genome], _: &NeatConfig) {
+        for genome in genomes.iter_mut() {
+            let mut net = FeedForwardNetwork::create(genome);
+            let error: f64 = XOR.iter()
+                .map(|(x, y)| (net.activate(x)[0] - y).powi(2))
+                .sum();
+            genome.set_fitness(4.0 - error);
+        }
+    }
+
+    #[test]
+    fn population_size_stays_bounded() {
+        let config = NeatConfig {
+            no_fitness_termination: true,
+            ..xor_config(1)
+        };
+        let pop_size = config.pop_size;
+        let mut population = Population::new(config);
+        for _ in 0..30 {
+            population.run(xor_fitness, Some(1));
+            assert!(!population.genomes().is_empty());
+            assert!(population.genomes().len() <= pop_size,
+                "{} genomes in generation {}", population.genomes().len(),
+                population.get_generation());
+        }
+    }
+
+    #[test]
+    fn many_small_species_do_not_grow_the_population() {
+        // every genome founds its own species
+        let config = NeatConfig {
+            no_fitness_termination: true,
+            compatibility_threshold: 1e-9,
+            pop_size: 40,
+            ..xor_config(2)
+        };
+        let mut population = Population::new(config);
+        for _ in 0..10 {
+            population.run(xor_fitness, Some(1));
+            assert!(population.genomes().len() <= 40);
+        }
+    }
+
+    /// Best and mean fitness of every generation, and the final genomes.
+    fn seeded_run(seed: u64) -> (Vec<(f64, f64)>, HashMap<usize, Genome>) {
+        let mut population = Population::new(xor_config(seed));
+        let mut trajectory = Vec::new();
+        for _ in 0..20 {
+            population.run(|genomes: &mut [&mut Genome], config: &NeatConfig| {
+                xor_fitness(genomes, config);
+                let fitnesses: Vec<f64> = genomes.iter()
+                    .map(|g| g.get_fitness())
+                    .collect();
+                trajectory.push((
+                    FitnessFunction::Max.apply(&fitnesses),
+                    FitnessFunction::Mean.apply(&fitnesses),
+                ));
+            }, Some(1));
+        }
+        (trajectory, population.genomes().clone())
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let (trajectory, genomes) = seeded_run(7);
+        assert_eq!(trajectory.len(), 20);
+        assert_eq!(seeded_run(7), (trajectory.clone(), genomes));
+        assert_ne!(seeded_run(8).0, trajectory);
+    }
+}
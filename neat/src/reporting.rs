@@ -0,0 +1,342 @@
+//! Hooks into the evolutionary loop of a [`Population`].
+//!
+//! [`Population`]: crate::population::Population
+
+use std::{
+    fmt,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use hashbrown::HashMap;
+
+use crate::{
+    checkpoint::CheckpointError,
+    config::NeatConfig,
+    genome::Genome,
+    species::{Species, SpeciesSet},
+};
+
+/// Observer of a running population. Every callback does nothing by default,
+/// so implementations only override what they are interested in.
+#[allow(unused_variables)]
+pub trait Reporter {
+    fn start_generation(&mut self, generation: usize) {}
+
+    /// Called once every genome has been assigned a fitness.
+    fn post_evaluate(&mut self, config: &NeatConfig, 
+            genomes: &HashMap<usize, Genome>, species: &SpeciesSet, 
+            best: &Genome) {}
+
+    /// Called with the newly bred generation, before it is speciated.
+    fn post_reproduction(&mut self, config: &NeatConfig, 
+            genomes: &HashMap<usize, Genome>, species: &SpeciesSet) {}
+
+    /// Called for every species removed because it stopped improving.
+    fn species_stagnant(&mut self, key: usize, species: &Species) {}
+
+    /// Called when the population reaches `config.fitness_threshold`, with
+    /// its best genome.
+    fn found_solution(&mut self, config: &NeatConfig, generation: usize, 
+            best: &Genome) {}
+
+    /// Called when every species stagnated at once.
+    fn complete_extinction(&mut self) {}
+
+    /// Called after the next generation has been bred and speciated, with
+    /// `generation` still being the one that was just evaluated.
+    fn end_generation(&mut self, config: &NeatConfig, generation: usize,
+            genomes: &HashMap<usize, Genome>, species: &SpeciesSet) {}
+
+    /// Called when the checkpointer saved the population to `path`, with
+    /// `generation` being the one about to be evaluated.
+    fn checkpoint_saved(&mut self, generation: usize, path: &Path) {}
+
+    /// Called when the checkpointer failed to save the population. The run
+    /// continues regardless.
+    fn checkpoint_failed(&mut self, generation: usize, 
+            error: &CheckpointError) {}
+}
+
+/// Lets a reporter be shared with the caller, e.g. to read collected data
+/// after the run.
+impl<R: Reporter> Reporter for Arc<Mutex<R>> {
+    fn start_generation(&mut self, generation: usize) {
+        self.lock().unwrap().start_generation(generation);
+    }
+
+    fn post_evaluate(&mut self, config: &NeatConfig, 
+            genomes: &HashMap<usize, Genome>, species: &SpeciesSet, 
+            best: &Genome) {
+        self.lock().unwrap().post_evaluate(config, genomes, species, best);
+    }
+
+    fn post_reproduction(&mut self, config: &NeatConfig, 
+            genomes: &HashMap<usize, Genome>, species: &SpeciesSet) {
+        self.lock().unwrap().post_reproduction(config, genomes, species);
+    }
+
+    fn species_stagnant(&mut self, key: usize, species: &Species) {
+        self.lock().unwrap().species_stagnant(key, species);
+    }
+
+    fn found_solution(&mut self, config: &NeatConfig, generation: usize, 
+            best: &Genome) {
+        self.lock().unwrap().found_solution(config, generation, best);
+    }
+
+    fn complete_extinction(&mut self) {
+        self.lock().unwrap().complete_extinction();
+    }
+
+    fn end_generation(&mut self, config: &NeatConfig, generation: usize,
+            genomes: &HashMap<usize, Genome>, species: &SpeciesSet) {
+        self.lock().unwrap().end_generation(config, generation, genomes,
+            species);
+    }
+
+    fn checkpoint_saved(&mut self, generation: usize, path: &Path) {
+        self.lock().unwrap().checkpoint_saved(generation, path);
+    }
+
+    fn checkpoint_failed(&mut self, generation: usize, 
+            error: &CheckpointError) {
+        self.lock().unwrap().checkpoint_failed(generation, error);
+    }
+}
+
+/// All reporters attached to a population, called in the order they were
+/// added.
+#[derive(Default)]
+pub struct ReporterSet {
+    reporters: Vec<Box<dyn Reporter + Send>>,
+}
+
+impl fmt::Debug for ReporterSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ReporterSet({} reporters)", self.reporters.len())
+    }
+}
+
+/// Reporters are attached to one run only, so a clone starts without any.
+impl Clone for ReporterSet {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl ReporterSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<R: Reporter + Send + 'static>(&mut self, reporter: R) {
+        self.reporters.push(Box::new(reporter));
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.reporters.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.reporters.is_empty()
+    }
+}
+
+impl Reporter for ReporterSet {
+    fn start_generation(&mut self, generation: usize) {
+        for r in self.reporters.iter_mut() {
+            r.start_generation(generation);
+        }
+    }
+
+    fn post_evaluate(&mut self, config: &NeatConfig, 
+            genomes: &HashMap<usize, Genome>, species: &SpeciesSet, 
+            best: &Genome) {
+        for r in self.reporters.iter_mut() {
+            r.post_evaluate(config, genomes, species, best);
+        }
+    }
+
+    fn post_reproduction(&mut self, config: &NeatConfig, 
+            genomes: &HashMap<usize, Genome>, species: &SpeciesSet) {
+        for r in self.reporters.iter_mut() {
+            r.post_reproduction(config, genomes, species);
+        }
+    }
+
+    fn species_stagnant(&mut self, key: usize, species: &Species) {
+        for r in self.reporters.iter_mut() {
+            r.species_stagnant(key, species);
+        }
+    }
+
+    fn found_solution(&mut self, config: &NeatConfig, generation: usize, 
+            best: &Genome) {
+        for r in self.reporters.iter_mut() {
+            r.found_solution(config, generation, best);
+        }
+    }
+
+    fn complete_extinction(&mut self) {
+        for r in self.reporters.iter_mut() {
+            r.complete_extinction();
+        }
+    }
+
+    fn end_generation(&mut self, config: &NeatConfig, generation: usize,
+            genomes: &HashMap<usize, Genome>, species: &SpeciesSet) {
+        for r in self.reporters.iter_mut() {
+            r.end_generation(config, generation, genomes, species);
+        }
+    }
+
+    fn checkpoint_saved(&mut self, generation: usize, path: &Path) {
+        for r in self.reporters.iter_mut() {
+            r.checkpoint_saved(generation, path);
+        }
+    }
+
+    fn checkpoint_failed(&mut self, generation: usize, 
+            error: &CheckpointError) {
+        for r in self.reporters.iter_mut() {
+            r.checkpoint_failed(generation, error);
+        }
+    }
+}
+
+/// Mean and population standard deviation of `values`.
+pub(crate) fn mean_stdev(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, var.sqrt())
+}
+
+/// Prints the progress of a run to stdout, in the format of neat-python's
+/// `StdOutReporter`.
+#[derive(Debug, Clone)]
+pub struct StdOutReporter {
+    show_species_detail: bool,
+
+    generation:       usize,
+    generation_start: Option<Instant>,
+    generation_times: Vec<Duration>,
+    num_extinctions:  usize,
+}
+
+impl StdOutReporter {
+    /// With `show_species_detail` set, a table of all species is printed 
+    /// every generation instead of just their number.
+    pub fn new(show_species_detail: bool) -> Self {
+        Self {
+            show_species_detail,
+            generation: 0,
+            generation_start: None,
+            generation_times: Vec::new(),
+            num_extinctions: 0,
+        }
+    }
+}
+
+impl Reporter for StdOutReporter {
+    fn start_generation(&mut self, generation: usize) {
+        self.generation = generation;
+        println!("\n ****** Running generation {} ****** \n", generation);
+        self.generation_start = Some(Instant::now());
+    }
+
+    fn post_evaluate(&mut self, _config: &NeatConfig, 
+            genomes: &HashMap<usize, Genome>, species: &SpeciesSet, 
+            best: &Genome) {
+        let fitnesses: Vec<f64> = genomes.values()
+            .map(|g| g.get_fitness())
+            .collect();
+        let (mean, stdev) = mean_stdev(&fitnesses);
+        println!("Population's average fitness: {:.5} stdev: {:.5}", mean,
+            stdev);
+
+        let enabled = best.connections().values()
+            .filter(|c| c.is_enabled())
+            .count();
+        let best_species = species.get_species_id(best.get_id())
+            .map_or_else(|| "-".to_string(), |k| k.to_string());
+        println!("Best fitness: {:.5} - size: ({}, {}) - species {} - id {}",
+            best.get_fitness(), best.nodes().len(), enabled, best_species,
+            best.get_id());
+    }
+
+    fn species_stagnant(&mut self, key: usize, species: &Species) {
+        if self.show_species_detail {
+            println!("\nSpecies {} with {} members is stagnated: removing it",
+                key, species.members().len());
+        }
+    }
+
+    fn found_solution(&mut self, _config: &NeatConfig, generation: usize, 
+            best: &Genome) {
+        println!("\nBest individual in generation {} meets fitness \
+            threshold - size: ({}, {})", generation, best.nodes().len(),
+            best.connections().len());
+    }
+
+    fn complete_extinction(&mut self) {
+        self.num_extinctions += 1;
+        println!("All species extinct.");
+    }
+
+    fn end_generation(&mut self, _config: &NeatConfig, generation: usize,
+            genomes: &HashMap<usize, Genome>, species: &SpeciesSet) {
+        println!("Population of {} members in {} species", genomes.len(),
+            species.len());
+        if self.show_species_detail {
+            let mut keys: Vec<usize> = species.species().keys().copied()
+                .collect();
+            keys.sort_unstable();
+
+            // the species already belong to the next generation
+            let generation = generation + 1;
+            println!("   ID   age  size   fitness   adj fit  stag");
+            println!("  ====  ===  ====  =========  =======  ====");
+            for key in keys {
+                let s = &species.species()[&key];
+                let fitness = s.get_fitness()
+                    .map_or_else(|| "--".to_string(), |f| format!("{:.3}", f));
+                let adjusted = s.get_adjusted_fitness()
+                    .map_or_else(|| "--".to_string(), |f| format!("{:.3}", f));
+                let stagnation = generation.saturating_sub(
+                    s.get_last_improved());
+                println!("  {:>4}  {:>3}  {:>4}  {:>9}  {:>7}  {:>4}", key,
+                    s.get_age(generation), s.members().len(), fitness, 
+                    adjusted, stagnation);
+            }
+        }
+        println!("Total extinctions: {}", self.num_extinctions);
+
+        if let Some(start) = self.generation_start.take() {
+            self.generation_times.push(start.elapsed());
+            if self.generation_times.len() > 10 {
+                self.generation_times.remove(0);
+            }
+            let average = self.generation_times.iter().sum::<Duration>()
+                .as_secs_f64() / self.generation_times.len() as f64;
+            println!("Generation time: {:.3} sec ({:.3} average)",
+                start.elapsed().as_secs_f64(), average);
+        }
+    }
+
+    fn checkpoint_saved(&mut self, _generation: usize, path: &Path) {
+        println!("Saving checkpoint to {}", path.display());
+    }
+
+    fn checkpoint_failed(&mut self, _generation: usize, 
+            error: &CheckpointError) {
+        println!("Failed to save checkpoint: {}", error);
+    }
+}
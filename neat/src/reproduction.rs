@@ -0,0 +1,259 @@
+use hashbrown::HashMap;
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::NeatConfig,
+    genome::Genome,
+    innovation::InnovationTracker,
+    species::SpeciesSet,
+};
+
+/// Creates new generations of genomes from the species of the previous one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Reproduction {
+    next_genome_id: usize,
+}
+
+impl Reproduction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    fn get_new_genome_id(&mut self) -> usize {
+        self.next_genome_id += 1;
+        self.next_genome_id - 1
+    }
+
+    #[inline]
+    pub(crate) fn set_next_genome_id(&mut self, id: usize) {
+        self.next_genome_id = id;
+    }
+
+    /// Creates `num_genomes` brand new genomes.
+    pub fn create_new<R: Rng>(&mut self, config: &NeatConfig, 
+            num_genomes: usize, rng: &mut R) -> HashMap<usize, Genome> {
+        (0..num_genomes).map(|_| {
+            let id = self.get_new_genome_id();
+            (id, Genome::new(id, config, rng))
+        }).collect()
+    }
+
+    /// Computes how many offspring each species gets. Species sizes move
+    /// half way from their previous size towards their share of the
+    /// population, which is proportional to their adjusted fitness, and are
+    /// then normalised to `pop_size`.
+    ///
+    /// Every species gets at least `min_species_size` offspring as long as
+    /// that fits into `pop_size`. If it does not, the largest allocations 
+    /// are trimmed first and then the least fit species get none at all, so
+    /// the total never exceeds `pop_size`.
+    fn compute_spawn(adjusted_fitness: &[f64], previous_sizes: &[usize],
+            pop_size: usize, min_species_size: usize) -> Vec<usize> {
+        let af_sum: f64 = adjusted_fitness.iter().sum();
+
+        let spawn_amounts: Vec<f64> = adjusted_fitness.iter()
+            .zip(previous_sizes)
+            .map(|(&af, &ps)| {
+                let s = if af_sum > 0.0 {
+                    (af / af_sum * pop_size as f64).max(min_species_size as f64)
+                } else {
+                    min_species_size as f64
+                };
+
+                let ps = ps as f64;
+                let d = (s - ps) * 0.5;
+                let c = d.round();
+                if c.abs() > 0.0 {
+                    ps + c
+                } else if d > 0.0 {
+                    ps + 1.0
+                } else if d < 0.0 {
+                    ps - 1.0
+                } else {
+                    ps
+                }
+            })
+            .collect();
+
+        let total_spawn: f64 = spawn_amounts.iter().sum();
+        let mut spawn: Vec<usize> = if total_spawn > 0.0 || 
+                spawn_amounts.is_empty() {
+            let norm = pop_size as f64 / total_spawn;
+            spawn_amounts.into_iter()
+                .map(|n| ((n * norm).round() as usize).max(min_species_size))
+                .collect()
+        } else {
+            // every species shrank to nothing, share the population evenly
+            let n = pop_size.div_ceil(spawn_amounts.len());
+            vec![n.max(min_species_size); spawn_amounts.len()]
+        };
+
+        let mut total: usize = spawn.iter().sum();
+        while total > pop_size {
+            let largest = (0..spawn.len())
+                .filter(|&i| spawn[i] > min_species_size)
+                .max_by_key(|&i| (spawn[i], std::cmp::Reverse(i)));
+            if let Some(i) = largest {
+                spawn[i] -= 1;
+                total -= 1;
+                continue;
+            }
+
+            let alive: Vec<usize> = (0..spawn.len())
+                .filter(|&i| spawn[i] > 0)
+                .collect();
+            if alive.len() == 1 {
+                // a single species larger than the whole population
+                spawn[alive[0]] = pop_size;
+                break;
+            }
+            let least_fit = alive.into_iter()
+                .min_by(|&a, &b| adjusted_fitness[a]
+                    .total_cmp(&adjusted_fitness[b])
+                    .then(b.cmp(&a)))
+                .unwrap();
+            total -= spawn[least_fit];
+            spawn[least_fit] = 0;
+        }
+        spawn
+    }
+
+    /// Breeds the next generation from the species in `species`.
+    ///
+    /// Each species is assigned an adjusted fitness, its mean member fitness
+    /// normalised over the fitness range of the whole population, and gets a
+    /// number of offspring proportional to it, but at least 
+    /// `config.min_species_size` while that fits into `config.pop_size`; the
+    /// new generation never has more than `config.pop_size` genomes. The 
+    /// best `config.elitism` members of a species are copied over unchanged,
+    /// the remaining offspring are children of the top 
+    /// `config.survival_threshold` fraction, mutated with innovation numbers
+    /// from `innovations`.
+    pub fn reproduce<R: Rng>(&mut self, config: &NeatConfig, 
+            species: &mut SpeciesSet, genomes: &HashMap<usize, Genome>, 
+            innovations: &mut InnovationTracker, rng: &mut R) 
+            -> HashMap<usize, Genome> {
+        let mut keys: Vec<usize> = species.species().keys().copied().collect();
+        keys.sort();
+        if keys.is_empty() {
+            return HashMap::new();
+        }
+
+        let all_fitnesses: Vec<f64> = keys.iter()
+            .flat_map(|k| species.species()[k].get_fitnesses(genomes))
+            .collect();
+        let min_fitness = all_fitnesses.iter().copied()
+            .fold(f64::INFINITY, f64::min);
+        let max_fitness = all_fitnesses.iter().copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        // do not let the range get too small, so that small differences in
+        // fitness are not blown up
+        let fitness_range = (max_fitness - min_fitness).max(1.0);
+
+        let mut adjusted_fitness = Vec::with_capacity(keys.len());
+        let mut previous_sizes = Vec::with_capacity(keys.len());
+        for key in keys.iter() {
+            let s = species.species_mut().get_mut(key).unwrap();
+            let fitnesses = s.get_fitnesses(genomes);
+            let mean = fitnesses.iter().sum::<f64>() / fitnesses.len() as f64;
+            let af = (mean - min_fitness) / fitness_range;
+            s.set_adjusted_fitness(af);
+            adjusted_fitness.push(af);
+            previous_sizes.push(fitnesses.len());
+        }
+
+        let min_species_size = config.min_species_size.max(config.elitism);
+        let spawn_amounts = Self::compute_spawn(&adjusted_fitness, 
+            &previous_sizes, config.pop_size, min_species_size);
+
+        let mut ret = HashMap::new();
+        for (key, mut spawn) in keys.iter().zip(spawn_amounts) {
+            if spawn == 0 {
+                continue;
+            }
+
+            let mut members: Vec<&Genome> = species.species()[key].members()
+                .iter()
+                .map(|m| &genomes[m])
+                .collect();
+            members.sort_by(|a, b| b.get_fitness().total_cmp(&a.get_fitness())
+                .then(a.get_id().cmp(&b.get_id())));
+
+            for elite in members.iter().take(config.elitism.min(spawn)) {
+                ret.insert(elite.get_id(), (*elite).clone());
+                spawn -= 1;
+            }
+            if spawn == 0 {
+                continue;
+            }
+
+            let cutoff = (config.survival_threshold * members.len() as f64)
+                .ceil() as usize;
+            members.truncate(cutoff.max(2));
+
+            for _ in 0..spawn {
+                let parent1 = members.choose(rng).unwrap();
+                let parent2 = members.choose(rng).unwrap();
+                let id = self.get_new_genome_id();
+                let mut child = Genome::crossover(id, parent1, parent2, rng);
+                child.mutate(config, innovations, rng);
+                ret.insert(id, child);
+            }
+        }
+
+        ret
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawn_follows_adjusted_fitness() {
+        let spawn = Reproduction::compute_spawn(&[0.0, 1.0], &[75, 75], 150, 
+            2);
+        assert!(spawn[1] > spawn[0]);
+        assert!(spawn[0] >= 2);
+        assert!(spawn.iter().sum::<usize>() <= 150);
+    }
+
+    #[test]
+    fn spawn_never_exceeds_pop_size() {
+        for num_species in [1, 10, 74, 75, 76, 100, 150, 300] {
+            let adjusted: Vec<f64> = (0..num_species)
+                .map(|i| i as f64 / num_species as f64)
+                .collect();
+            let previous = vec![150 / num_species + 1; num_species];
+            let spawn = Reproduction::compute_spawn(&adjusted, &previous, 
+                150, 2);
+            assert!(spawn.iter().sum::<usize>() <= 150, 
+                "{} species spawn {:?}", num_species, spawn);
+            assert!(spawn.iter().all(|&n| n == 0 || n >= 2));
+        }
+    }
+
+    #[test]
+    fn least_fit_species_are_dropped_first() {
+        let spawn = Reproduction::compute_spawn(&[0.9, 0.1, 0.5], &[2, 2, 2],
+            4, 2);
+        assert_eq!(spawn, [2, 0, 2]);
+    }
+
+    #[test]
+    fn single_species_larger_than_population_is_cut() {
+        let spawn = Reproduction::compute_spawn(&[1.0], &[10], 3, 5);
+        assert_eq!(spawn, [3]);
+    }
+
+    #[test]
+    fn species_that_all_shrink_to_nothing_share_the_population() {
+        let spawn = Reproduction::compute_spawn(&[0.0, 0.0], &[1, 1], 10, 0);
+        assert_eq!(spawn, [5, 5]);
+        let spawn = Reproduction::compute_spawn(&[0.0; 3], &[1; 3], 10, 0);
+        assert_eq!(spawn.iter().sum::<usize>(), 10);
+    }
+}
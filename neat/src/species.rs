@@ -0,0 +1,356 @@
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::NeatConfig, genome::Genome};
+
+/// A group of genomes that are close to each other in terms of
+/// [`Genome::distance`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Species {
+    key:           usize,
+    created:       usize,
+    last_improved: usize,
+
+    representative: Genome,
+    members:        Vec<usize>,
+
+    fitness:          Option<f64>,
+    adjusted_fitness: Option<f64>,
+    fitness_history:  Vec<f64>,
+}
+
+impl Species {
+    pub fn new(key: usize, generation: usize, representative: Genome) -> Self {
+        Self {
+            key,
+            created: generation,
+            last_improved: generation,
+            members: vec![representative.get_id()],
+            representative,
+            fitness: None,
+            adjusted_fitness: None,
+            fitness_history: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, representative: Genome, members: Vec<usize>) {
+        self.representative = representative;
+        self.members = members;
+    }
+
+    #[inline]
+    pub fn get_key(&self) -> usize {
+        self.key
+    }
+
+    /// Number of generations this species has existed for.
+    #[inline]
+    pub fn get_age(&self, generation: usize) -> usize {
+        generation - self.created
+    }
+
+    #[inline]
+    pub fn representative(&self) -> &Genome {
+        &self.representative
+    }
+
+    /// Ids of the genomes belonging to this species.
+    #[inline]
+    pub fn members(&self) -> &[usize] {
+        &self.members
+    }
+
+    /// Fitness values of all members.
+    pub fn get_fitnesses(&self, genomes: &HashMap<usize, Genome>) -> Vec<f64> {
+        self.members.iter().map(|m| genomes[m].get_fitness()).collect()
+    }
+
+    /// Fitness of the species as computed by `config.species_fitness_func`
+    /// during the last stagnation update.
+    #[inline]
+    pub fn get_fitness(&self) -> Option<f64> {
+        self.fitness
+    }
+
+    #[inline]
+    pub fn get_adjusted_fitness(&self) -> Option<f64> {
+        self.adjusted_fitness
+    }
+
+    #[inline]
+    pub fn set_adjusted_fitness(&mut self, fitness: f64) {
+        self.adjusted_fitness = Some(fitness);
+    }
+
+    /// Species fitness of every generation this species was evaluated in.
+    #[inline]
+    pub fn fitness_history(&self) -> &[f64] {
+        &self.fitness_history
+    }
+
+    /// Generation in which the species fitness last exceeded its previous
+    /// best.
+    #[inline]
+    pub fn get_last_improved(&self) -> usize {
+        self.last_improved
+    }
+
+    /// Records the fitness reached in `generation`.
+    pub(crate) fn record_fitness(&mut self, fitness: f64, generation: usize) {
+        let prev_best = self.fitness_history.iter().copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if fitness > prev_best {
+            self.last_improved = generation;
+        }
+        self.fitness = Some(fitness);
+        self.adjusted_fitness = None;
+        self.fitness_history.push(fitness);
+    }
+}
+
+/// Partition of a population into species.
+///
+/// Species persist across generations: every species keeps a representative
+/// which new genomes are compared against, and the compatibility threshold
+/// can be adjusted each generation to steer towards
+/// `config.target_num_species`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeciesSet {
+    species:           HashMap<usize, Species>,
+    genome_to_species: HashMap<usize, usize>,
+    next_key:          usize,
+
+    compatibility_threshold: f64,
+}
+
+impl SpeciesSet {
+    pub fn new(config: &NeatConfig) -> Self {
+        Self {
+            species: HashMap::new(),
+            genome_to_species: HashMap::new(),
+            next_key: 0,
+            compatibility_threshold: config.compatibility_threshold,
+        }
+    }
+
+    /// Sorts `genomes` into species.
+    ///
+    /// Every existing species first picks the genome closest to its old
+    /// representative as its new representative. The remaining genomes join
+    /// the species with the closest representative within the compatibility
+    /// threshold, or found a new species if there is none. Species without
+    /// any members are dropped.
+    pub fn speciate(&mut self, config: &NeatConfig,
+            genomes: &HashMap<usize, Genome>, generation: usize) {
+        let mut unspeciated: Vec<usize> = genomes.keys().copied().collect();
+        unspeciated.sort();
+
+        let mut keys: Vec<usize> = self.species.keys().copied().collect();
+        keys.sort();
+
+        let mut representatives: Vec<(usize, usize)> = Vec::new();
+        let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+        for key in keys {
+            let old = &self.species[&key].representative;
+            let closest = unspeciated.iter().enumerate()
+                .map(|(i, id)| (i, old.distance(&genomes[id], config)))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((i, _)) = closest {
+                let id = unspeciated.remove(i);
+                representatives.push((key, id));
+                members.insert(key, vec![id]);
+            }
+        }
+
+        for id in unspeciated {
+            let genome = &genomes[&id];
+            let closest = representatives.iter()
+                .map(|&(key, rep)| {
+                    (key, genomes[&rep].distance(genome, config))
+                })
+                .filter(|&(_, d)| d < self.compatibility_threshold)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            match closest {
+                Some((key, _)) => members.get_mut(&key).unwrap().push(id),
+                None => {
+                    let key = self.next_key;
+                    self.next_key += 1;
+                    representatives.push((key, id));
+                    members.insert(key, vec![id]);
+                }
+            }
+        }
+
+        self.genome_to_species.clear();
+        let mut species = HashMap::new();
+        for (key, rep) in representatives {
+            let members = members.remove(&key).unwrap();
+            for id in members.iter() {
+                self.genome_to_species.insert(*id, key);
+            }
+
+            let representative = genomes[&rep].clone();
+            let mut s = self.species.remove(&key).unwrap_or_else(|| 
+                Species::new(key, generation, representative.clone()));
+            s.update(representative, members);
+            species.insert(key, s);
+        }
+        self.species = species;
+
+        self.adjust_compatibility_threshold(config);
+    }
+
+    fn adjust_compatibility_threshold(&mut self, config: &NeatConfig) {
+        if config.target_num_species == 0 {
+            return;
+        }
+
+        if self.species.len() < config.target_num_species {
+            self.compatibility_threshold -= 
+                config.compatibility_threshold_modifier;
+        } else if self.species.len() > config.target_num_species {
+            self.compatibility_threshold += 
+                config.compatibility_threshold_modifier;
+        }
+        self.compatibility_threshold = self.compatibility_threshold
+            .max(config.compatibility_threshold_min);
+    }
+
+    #[inline]
+    pub fn species(&self) -> &HashMap<usize, Species> {
+        &self.species
+    }
+
+    #[inline]
+    pub fn species_mut(&mut self) -> &mut HashMap<usize, Species> {
+        &mut self.species
+    }
+
+    /// Removes a species together with the membership of its genomes.
+    pub fn remove(&mut self, key: usize) -> Option<Species> {
+        let ret = self.species.remove(&key)?;
+        for id in ret.members.iter() {
+            self.genome_to_species.remove(id);
+        }
+        Some(ret)
+    }
+
+    /// Key of the species the genome with the given id belongs to.
+    #[inline]
+    pub fn get_species_id(&self, genome_id: usize) -> Option<usize> {
+        self.genome_to_species.get(&genome_id).copied()
+    }
+
+    /// The threshold used for the next call to [`SpeciesSet::speciate`].
+    #[inline]
+    pub fn get_compatibility_threshold(&self) -> f64 {
+        self.compatibility_threshold
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.species.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.species.is_empty()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::config::InitialConnection;
+
+    fn config() -> NeatConfig {
+        NeatConfig {
+            num_inputs: 1,
+            num_outputs: 1,
+            initial_connection: InitialConnection::FullDirect,
+            bias_init_stdev: 0.0,
+            weight_init_stdev: 0.0,
+            compatibility_threshold: 3.0,
+            ..NeatConfig::default()
+        }
+    }
+
+    /// Genomes whose only connection has the weight given for their id.
+    fn genomes(weights: &[(usize, f64)]) -> HashMap<usize, Genome> {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        weights.iter()
+            .map(|&(id, weight)| {
+                let config = NeatConfig {
+                    weight_init_mean: weight,
+                    ..config()
+                };
+                (id, Genome::new(id, &config, &mut rng))
+            })
+            .collect()
+    }
+
+    fn species_of(set: &SpeciesSet, ids: &[usize]) -> Vec<usize> {
+        ids.iter().map(|&id| set.get_species_id(id).unwrap()).collect()
+    }
+
+    #[test]
+    fn distant_genomes_found_new_species() {
+        let config = config();
+        let genomes = genomes(&[(0, 0.0), (1, 20.0), (2, 0.5), (3, 21.0)]);
+        let mut set = SpeciesSet::new(&config);
+        set.speciate(&config, &genomes, 0);
+
+        assert_eq!(set.len(), 2);
+        assert_eq!(species_of(&set, &[0, 1, 2, 3]), [0, 1, 0, 1]);
+        let s = &set.species()[&0];
+        assert_eq!(s.representative().get_id(), 0);
+        assert_eq!(s.members(), [0, 2]);
+        assert_eq!(set.species()[&1].members(), [1, 3]);
+    }
+
+    #[test]
+    fn representatives_move_to_the_closest_genome() {
+        let config = config();
+        let mut set = SpeciesSet::new(&config);
+        set.speciate(&config, &genomes(&[(0, 0.0), (1, 20.0)]), 0);
+
+        // the next generation drifted, and adds a genome far from both
+        let next = genomes(&[(2, 1.0), (3, 0.5), (4, 18.0), (5, -20.0)]);
+        set.speciate(&config, &next, 1);
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.species()[&0].representative().get_id(), 3);
+        assert_eq!(set.species()[&1].representative().get_id(), 4);
+        assert_eq!(species_of(&set, &[2, 3, 4, 5]), [0, 0, 1, 2]);
+        assert_eq!(set.species()[&2].get_age(1), 0);
+        assert_eq!(set.species()[&0].get_age(1), 1);
+        assert_eq!(set.get_species_id(0), None);
+    }
+
+    #[test]
+    fn threshold_steers_towards_the_target_number_of_species() {
+        let genomes = genomes(&[(0, 0.0), (1, 20.0)]);
+        let steer = |target_num_species: usize| {
+            let config = NeatConfig {
+                target_num_species,
+                compatibility_threshold_modifier: 0.5,
+                compatibility_threshold_min: 2.8,
+                ..config()
+            };
+            let mut set = SpeciesSet::new(&config);
+            set.speciate(&config, &genomes, 0);
+            let first = set.get_compatibility_threshold();
+            set.speciate(&config, &genomes, 1);
+            (first, set.get_compatibility_threshold())
+        };
+
+        // too few species lower the threshold, down to the minimum
+        assert_eq!(steer(3), (2.8, 2.8));
+        assert_eq!(steer(1), (3.5, 4.0));
+        assert_eq!(steer(2), (3.0, 3.0));
+        assert_eq!(steer(0), (3.0, 3.0));
+    }
+}
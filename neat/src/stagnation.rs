@@ -0,0 +1,123 @@
+use hashbrown::HashMap;
+
+use crate::{config::NeatConfig, genome::Genome, species::SpeciesSet};
+
+/// Keeps track of species that stopped improving.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stagnation;
+
+impl Stagnation {
+    /// Computes the fitness of every species with
+    /// `config.species_fitness_func`, appends it to the species' history and
+    /// decides which species are stagnant.
+    ///
+    /// A species is stagnant if it has not improved for 
+    /// `config.max_stagnation` generations, unless it is among the
+    /// `config.species_elitism` best species. Returns the species keys
+    /// together with their stagnation flag, sorted by ascending fitness.
+    pub fn update(config: &NeatConfig, species_set: &mut SpeciesSet,
+            genomes: &HashMap<usize, Genome>, generation: usize) 
+            -> Vec<(usize, bool)> {
+        let mut species_data: Vec<(usize, f64, usize)> = Vec::new();
+        for (key, s) in species_set.species_mut().iter_mut() {
+            let fitness = config.species_fitness_func
+                .apply(&s.get_fitnesses(genomes));
+            s.record_fitness(fitness, generation);
+            species_data.push((*key, fitness, s.get_last_improved()));
+        }
+        species_data.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+
+        let num_species = species_data.len();
+        let mut num_non_stagnant = num_species;
+        let mut ret = Vec::with_capacity(num_species);
+        for (idx, (key, _, last_improved)) in species_data.into_iter()
+                .enumerate() {
+            let stagnant_time = generation - last_improved;
+            let mut is_stagnant = num_non_stagnant > config.species_elitism &&
+                stagnant_time >= config.max_stagnation;
+            if num_species - idx <= config.species_elitism {
+                is_stagnant = false;
+            }
+            if is_stagnant {
+                num_non_stagnant -= 1;
+            }
+            ret.push((key, is_stagnant));
+        }
+
+        ret
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::config::InitialConnection;
+
+    /// Three species of one genome each, far apart from each other, whose
+    /// fitness is their id.
+    fn species(config: &NeatConfig) -> (SpeciesSet, HashMap<usize, Genome>) {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let genomes: HashMap<usize, Genome> = (0..3)
+            .map(|id| {
+                let config = NeatConfig {
+                    weight_init_mean: id as f64 * 20.0,
+                    ..config.clone()
+                };
+                let mut genome = Genome::new(id, &config, &mut rng);
+                genome.set_fitness(id as f64);
+                (id, genome)
+            })
+            .collect();
+        let mut species_set = SpeciesSet::new(config);
+        species_set.speciate(config, &genomes, 0);
+        assert_eq!(species_set.len(), 3);
+        (species_set, genomes)
+    }
+
+    fn config(species_elitism: usize) -> NeatConfig {
+        NeatConfig {
+            num_inputs: 1,
+            num_outputs: 1,
+            initial_connection: InitialConnection::FullDirect,
+            bias_init_stdev: 0.0,
+            weight_init_stdev: 0.0,
+            max_stagnation: 3,
+            species_elitism,
+            ..NeatConfig::default()
+        }
+    }
+
+    #[test]
+    fn species_stagnate_without_improvement() {
+        let config = config(0);
+        let (mut species_set, mut genomes) = species(&config);
+        let update = |species_set: &mut SpeciesSet, 
+                genomes: &HashMap<usize, Genome>, generation| {
+            Stagnation::update(&config, species_set, genomes, generation)
+        };
+        assert_eq!(update(&mut species_set, &genomes, 0), 
+            [(0, false), (1, false), (2, false)]);
+        assert_eq!(update(&mut species_set, &genomes, 2), 
+            [(0, false), (1, false), (2, false)]);
+
+        // species 1 improves, and is now the best one
+        genomes.get_mut(&1).unwrap().set_fitness(5.0);
+        assert_eq!(update(&mut species_set, &genomes, 3), 
+            [(0, true), (2, true), (1, false)]);
+        assert_eq!(species_set.species()[&1].get_last_improved(), 3);
+    }
+
+    #[test]
+    fn best_species_are_never_stagnant() {
+        let config = config(2);
+        let (mut species_set, genomes) = species(&config);
+        Stagnation::update(&config, &mut species_set, &genomes, 0);
+        assert_eq!(
+            Stagnation::update(&config, &mut species_set, &genomes, 10),
+            [(0, true), (1, false), (2, false)]);
+    }
+}
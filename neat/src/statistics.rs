@@ -0,0 +1,302 @@
+//! Collects fitness and speciation statistics over a run, in the same shape
+//! as neat-python's `StatisticsReporter`.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use hashbrown::HashMap;
+
+use crate::{
+    config::{FitnessFunction, NeatConfig},
+    genome::Genome,
+    reporting::{mean_stdev, Reporter},
+    species::SpeciesSet,
+};
+
+/// Fitness of every member of every species, in order of genome id.
+type SpeciesFitnesses = HashMap<usize, Vec<(usize, f64)>>;
+
+fn write_rows<P, I>(path: P, rows: I) -> io::Result<()>
+        where P: AsRef<Path>, I: IntoIterator<Item = String> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for row in rows {
+        writeln!(out, "{}", row)?;
+    }
+    out.flush()
+}
+
+/// Records, for every evaluated generation, the best genome and the fitness
+/// of each member of each species.
+///
+/// Attach it to a population through an `Arc<Mutex<_>>` to read the
+/// statistics once the run is over.
+#[derive(Debug, Clone, Default)]
+pub struct StatisticsReporter {
+    most_fit_genomes: Vec<Genome>,
+    generation_statistics: Vec<SpeciesFitnesses>,
+}
+
+impl StatisticsReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of generations recorded so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.most_fit_genomes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.most_fit_genomes.is_empty()
+    }
+
+    /// Best genome of every generation, in order.
+    #[inline]
+    pub fn most_fit_genomes(&self) -> &[Genome] {
+        &self.most_fit_genomes
+    }
+
+    /// Best genome seen over the whole run.
+    pub fn best_genome(&self) -> Option<&Genome> {
+        self.best_genomes(1).into_iter().next()
+    }
+
+    /// The `n` fittest of the per-generation best genomes, best first. A
+    /// genome that stayed the best for several generations is returned once
+    /// per generation.
+    pub fn best_genomes(&self, n: usize) -> Vec<&Genome> {
+        let mut genomes: Vec<&Genome> = self.most_fit_genomes.iter()
+            .collect();
+        genomes.sort_by(|a, b| b.get_fitness().total_cmp(&a.get_fitness()));
+        genomes.truncate(n);
+        genomes
+    }
+
+    /// Like [`StatisticsReporter::best_genomes`], but every genome id is 
+    /// returned at most once.
+    pub fn best_unique_genomes(&self, n: usize) -> Vec<&Genome> {
+        let mut unique: HashMap<usize, &Genome> = HashMap::new();
+        for g in self.most_fit_genomes.iter() {
+            unique.insert(g.get_id(), g);
+        }
+        let mut genomes: Vec<&Genome> = unique.into_values().collect();
+        genomes.sort_by(|a, b| b.get_fitness().total_cmp(&a.get_fitness())
+            .then(a.get_id().cmp(&b.get_id())));
+        genomes.truncate(n);
+        genomes
+    }
+
+    // `f` applied to the fitnesses of every generation, in order of genome
+    // id so that the result does not depend on the iteration order of maps
+    fn fitness_stat<F>(&self, f: F) -> Vec<f64> where F: Fn(&[f64]) -> f64 {
+        self.generation_statistics.iter()
+            .map(|stats| {
+                let mut members: Vec<(usize, f64)> = stats.values()
+                    .flatten()
+                    .copied()
+                    .collect();
+                members.sort_unstable_by_key(|&(id, _)| id);
+                let fitnesses: Vec<f64> = members.into_iter()
+                    .map(|(_, fitness)| fitness)
+                    .collect();
+                f(&fitnesses)
+            })
+            .collect()
+    }
+
+    /// Mean fitness of the population in every generation.
+    pub fn get_fitness_mean(&self) -> Vec<f64> {
+        self.fitness_stat(|v| mean_stdev(v).0)
+    }
+
+    /// Standard deviation of the fitness of the population in every
+    /// generation.
+    pub fn get_fitness_stdev(&self) -> Vec<f64> {
+        self.fitness_stat(|v| mean_stdev(v).1)
+    }
+
+    /// Median fitness of the population in every generation.
+    pub fn get_fitness_median(&self) -> Vec<f64> {
+        self.fitness_stat(|v| FitnessFunction::Median.apply(v))
+    }
+
+    // every species key that ever existed, in order
+    fn species_keys(&self) -> Vec<usize> {
+        let mut keys: Vec<usize> = self.generation_statistics.iter()
+            .flat_map(|stats| stats.keys().copied())
+            .collect();
+        keys.sort_unstable();
+        keys.dedup();
+        keys
+    }
+
+    /// Size of every species in every generation, with one column per 
+    /// species that ever existed and 0 for generations it was not alive in.
+    pub fn get_species_sizes(&self) -> Vec<Vec<usize>> {
+        let keys = self.species_keys();
+        self.generation_statistics.iter()
+            .map(|stats| keys.iter()
+                .map(|k| stats.get(k).map_or(0, |m| m.len()))
+                .collect())
+            .collect()
+    }
+
+    /// Mean member fitness of every species in every generation, laid out
+    /// like [`StatisticsReporter::get_species_sizes`] with `None` for 
+    /// generations a species was not alive in.
+    pub fn get_species_fitness(&self) -> Vec<Vec<Option<f64>>> {
+        let keys = self.species_keys();
+        self.generation_statistics.iter()
+            .map(|stats| keys.iter()
+                .map(|k| stats.get(k)
+                    .filter(|m| !m.is_empty())
+                    .map(|m| {
+                        let fitnesses: Vec<f64> = m.iter()
+                            .map(|&(_, fitness)| fitness)
+                            .collect();
+                        mean_stdev(&fitnesses).0
+                    }))
+                .collect())
+            .collect()
+    }
+
+    /// Writes the best and mean fitness of every generation, one generation
+    /// per line.
+    pub fn save_genome_fitness<P: AsRef<Path>>(&self, path: P, 
+            delimiter: &str) -> io::Result<()> {
+        let means = self.get_fitness_mean();
+        write_rows(path, self.most_fit_genomes.iter().zip(means)
+            .map(|(best, mean)| 
+                format!("{}{}{}", best.get_fitness(), delimiter, mean)))
+    }
+
+    /// Writes the output of [`StatisticsReporter::get_species_sizes`], one
+    /// generation per line.
+    pub fn save_species_count<P: AsRef<Path>>(&self, path: P, 
+            delimiter: &str) -> io::Result<()> {
+        write_rows(path, self.get_species_sizes().into_iter()
+            .map(|sizes| sizes.iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join(delimiter)))
+    }
+
+    /// Writes the output of [`StatisticsReporter::get_species_fitness`], one
+    /// generation per line, with `null_value` for missing species.
+    pub fn save_species_fitness<P: AsRef<Path>>(&self, path: P, 
+            delimiter: &str, null_value: &str) -> io::Result<()> {
+        write_rows(path, self.get_species_fitness().into_iter()
+            .map(|fitnesses| fitnesses.iter()
+                .map(|f| f.map_or_else(|| null_value.to_string(), 
+                    |f| f.to_string()))
+                .collect::<Vec<_>>()
+                .join(delimiter)))
+    }
+
+    /// Writes `fitness_history.csv`, `speciation.csv` and 
+    /// `species_fitness.csv` into `dir`, with the defaults of neat-python:
+    /// space delimited and `NA` for missing species.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> io::Result<()> {
+        let dir = dir.as_ref();
+        self.save_genome_fitness(dir.join("fitness_history.csv"), " ")?;
+        self.save_species_count(dir.join("speciation.csv"), " ")?;
+        self.save_species_fitness(dir.join("species_fitness.csv"), " ", "NA")
+    }
+}
+
+impl Reporter for StatisticsReporter {
+    fn post_evaluate(&mut self, _config: &NeatConfig, 
+            genomes: &HashMap<usize, Genome>, species: &SpeciesSet, 
+            best: &Genome) {
+        self.most_fit_genomes.push(best.clone());
+
+        let stats = species.species().iter()
+            .map(|(&key, s)| {
+                let mut members: Vec<(usize, f64)> = s.members().iter()
+                    .filter_map(|id| genomes.get(id))
+                    .map(|g| (g.get_id(), g.get_fitness()))
+                    .collect();
+                members.sort_unstable_by_key(|&(id, _)| id);
+                (key, members)
+            })
+            .collect();
+        self.generation_statistics.push(stats);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use crate::population::Population;
+
+    #[test]
+    fn saved_rows_match_the_recorded_generations() {
+        let config = NeatConfig {
+            seed: Some(3),
+            num_inputs: 2,
+            num_outputs: 1,
+            pop_size: 30,
+            no_fitness_termination: true,
+            ..NeatConfig::default()
+        };
+        let statistics = Arc::new(Mutex::new(StatisticsReporter::new()));
+        let mut population = Population::new(config);
+        population.add_reporter(Arc::clone(&statistics));
+        population.run(|genomes, _| {
+            for g in genomes.iter_mut() {
+                let fitness = g.get_id() as f64;
+                g.set_fitness(fitness);
+            }
+        }, Some(2));
+
+        let dir = std::env::temp_dir()
+            .join(format!("neat-statistics-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let statistics = statistics.lock().unwrap();
+        statistics.save(&dir).unwrap();
+        let rows = |name: &str| -> Vec<Vec<String>> {
+            fs::read_to_string(dir.join(name)).unwrap()
+                .lines()
+                .map(|l| l.split(' ').map(str::to_string).collect())
+                .collect()
+        };
+
+        let history = rows("fitness_history.csv");
+        assert_eq!(history.len(), 2);
+        let means = statistics.get_fitness_mean();
+        for (row, best) in history.iter().zip(statistics.most_fit_genomes()) {
+            assert_eq!(row[0], best.get_fitness().to_string());
+        }
+        assert_eq!(history[0][1], means[0].to_string());
+        // the first generation holds genomes 0 to 29
+        assert_eq!(means[0], 14.5);
+        assert_eq!(statistics.get_fitness_median()[0], 14.5);
+
+        let speciation = rows("speciation.csv");
+        let species_fitness = rows("species_fitness.csv");
+        assert_eq!(speciation.len(), 2);
+        assert_eq!(species_fitness.len(), 2);
+        for (sizes, fitnesses) in speciation.iter().zip(&species_fitness) {
+            assert_eq!(sizes.len(), fitnesses.len());
+            for (size, fitness) in sizes.iter().zip(fitnesses) {
+                assert_eq!(size == "0", fitness == "NA");
+            }
+            let total: usize = sizes.iter()
+                .map(|s| s.parse::<usize>().unwrap())
+                .sum();
+            assert_eq!(total, 30);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
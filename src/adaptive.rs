@@ -0,0 +1,226 @@
+//! Adaptive HyperNEAT: substrate networks whose weights keep changing during
+//! their lifetime.
+//!
+//! Besides the weight, the CPPN describes a plasticity rule for every
+//! connection through five extra outputs, following the weight output and
+//! the link expression output if there is one: the `A`, `B`, `C` and `D`
+//! coefficients and the learning rate of a generalised Hebbian rule.
+
+use neat::genome::Genome;
+
+use crate::{
+    config::HyperNeatConfig,
+    cppn::{Cppn, CppnError},
+    network::SubstrateNetwork,
+    substrate::Substrate,
+};
+
+/// Number of CPPN outputs describing a [`HebbianRule`].
+pub const RULE_OUTPUTS: usize = 5;
+
+/// Weight update `learning_rate * (a * pre * post + b * pre + c * post + d)`
+/// applied to a connection after every activation, where `pre` and `post`
+/// are the values of its source and target node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HebbianRule {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+
+    pub learning_rate: f64,
+}
+
+impl HebbianRule {
+    #[inline]
+    pub fn delta(&self, pre: f64, post: f64) -> f64 {
+        self.learning_rate * 
+            (self.a * pre * post + self.b * pre + self.c * post + self.d)
+    }
+}
+
+/// Substrate network that updates its weights after every activation.
+#[derive(Debug, Clone)]
+pub struct AdaptiveNetwork {
+    network:         SubstrateNetwork,
+    rules:           Vec<HebbianRule>,
+    initial_weights: Vec<f64>,
+    max_weight:      f64,
+}
+
+impl AdaptiveNetwork {
+    /// Builds the substrate network as [`SubstrateNetwork::create`] does and
+    /// attaches the plasticity rules of the same CPPN.
+    pub fn create(substrate: &Substrate, genome: &Genome, 
+            config: &HyperNeatConfig) -> Result<Self, CppnError> {
+        let network = SubstrateNetwork::create(substrate, genome, config)?;
+        Self::from_network(network, genome, config)
+    }
+
+    /// Attaches plasticity rules to an existing network, e.g. one created by
+    /// [`es_hyperneat::create_network`](crate::es_hyperneat::create_network),
+    /// by querying the CPPN compiled from `genome` for each connection.
+    /// Fails if the CPPN has no outputs for the rules.
+    pub fn from_network(network: SubstrateNetwork, genome: &Genome, 
+            config: &HyperNeatConfig) -> Result<Self, CppnError> {
+        let first = config.num_cppn_outputs();
+        Cppn::check_outputs(genome, first + RULE_OUTPUTS)?;
+        let mut cppn = Cppn::create(genome, config)?;
+        let coordinates = network.coordinates();
+
+        let rules = network.connections().iter()
+            .map(|c| {
+                let outputs = cppn.query(&coordinates[c.src], 
+                    &coordinates[c.dst]);
+                let o = |i: usize| outputs[first + i].clamp(-1.0, 1.0);
+                HebbianRule {
+                    a: o(0),
+                    b: o(1),
+                    c: o(2),
+                    d: o(3),
+                    learning_rate: o(4).abs() * config.learning_rate,
+                }
+            })
+            .collect();
+        let initial_weights = network.connections().iter()
+            .map(|c| c.weight)
+            .collect();
+
+        Ok(Self {
+            network,
+            rules,
+            initial_weights,
+            max_weight: config.max_weight,
+        })
+    }
+
+    #[inline]
+    pub fn network(&self) -> &SubstrateNetwork {
+        &self.network
+    }
+
+    /// Plasticity rule of every connection, in the order of
+    /// [`SubstrateNetwork::connections`].
+    #[inline]
+    pub fn rules(&self) -> &[HebbianRule] {
+        &self.rules
+    }
+
+    /// Activates the network and then updates every weight by its rule,
+    /// keeping it within `±config.max_weight`.
+    pub fn activate(&mut self, inputs: &[f64]) -> Vec<f64> {
+        let outputs = self.network.activate(inputs);
+
+        let values = self.network.values().to_vec();
+        let max_weight = self.max_weight;
+        for (conn, rule) in self.network.connections_mut().iter_mut()
+                .zip(self.rules.iter()) {
+            let delta = rule.delta(values[conn.src], values[conn.dst]);
+            conn.weight = (conn.weight + delta).clamp(-max_weight, max_weight);
+        }
+
+        outputs
+    }
+
+    /// Restores the weights generated by the CPPN, so that the next episode
+    /// starts from scratch.
+    pub fn reset(&mut self) {
+        for (conn, &w) in self.network.connections_mut().iter_mut()
+                .zip(self.initial_weights.iter()) {
+            conn.weight = w;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use neat::{
+        config::{InitialConnection, NeatConfig},
+        innovation::InnovationTracker,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    /// Network with a single connection, whose CPPN drives the weight, the
+    /// `a` coefficient and the learning rate from its bias input, leaving
+    /// `b`, `c` and `d` at 0.
+    fn network(config: &HyperNeatConfig) -> AdaptiveNetwork {
+        let neat_config = NeatConfig {
+            num_inputs: 5,
+            num_outputs: 1 + RULE_OUTPUTS,
+            initial_connection: InitialConnection::Unconnected,
+            bias_init_mean: 0.0,
+            bias_init_stdev: 0.0,
+            ..NeatConfig::default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&neat_config);
+        let mut genome = Genome::new(0, &neat_config, &mut rng);
+        let bias = genome.input_keys()[4];
+        for i in [0, 1, 5] {
+            let out = genome.output_keys()[i];
+            genome.add_connection(&mut innovations, bias, out, 1.0, true);
+        }
+
+        let substrate = Substrate::new(
+            vec![vec![0.0, -1.0]], 
+            vec![], 
+            vec![vec![0.0, 1.0]],
+        );
+        AdaptiveNetwork::create(&substrate, &genome, config).unwrap()
+    }
+
+    #[test]
+    fn hebbian_update_follows_pre_and_post_values() {
+        let config = HyperNeatConfig::default();
+        let mut network = network(&config);
+        let rule = network.rules()[0];
+        assert!(rule.a > 0.0 && rule.learning_rate > 0.0);
+        assert_eq!((rule.b, rule.c, rule.d), (0.0, 0.0, 0.0));
+
+        let before = network.network().connections()[0].weight;
+        network.activate(&[0.5]);
+        let values = network.network().values();
+        let (pre, post) = (values[0], values[1]);
+        let after = network.network().connections()[0].weight;
+        let expected = rule.learning_rate * rule.a * pre * post;
+        assert!((after - before - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn weights_are_clamped_and_reset() {
+        let config = HyperNeatConfig {
+            learning_rate: 100.0,
+            ..HyperNeatConfig::default()
+        };
+        let mut network = network(&config);
+        let initial = network.network().connections()[0].weight;
+        assert!(initial < config.max_weight);
+
+        for _ in 0..3 {
+            network.activate(&[1.0]);
+            assert_eq!(network.network().connections()[0].weight, 
+                config.max_weight);
+        }
+        network.reset();
+        assert_eq!(network.network().connections()[0].weight, initial);
+    }
+
+    #[test]
+    fn missing_rule_outputs_are_an_error() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let neat_config = NeatConfig {
+            num_inputs: 5,
+            num_outputs: 1,
+            ..NeatConfig::default()
+        };
+        let genome = Genome::new(0, &neat_config, &mut rng);
+        let substrate = Substrate::new(vec![vec![0.0, -1.0]], vec![], 
+            vec![vec![0.0, 1.0]]);
+        let config = HyperNeatConfig::default();
+        assert!(AdaptiveNetwork::create(&substrate, &genome, &config)
+            .is_err());
+    }
+}
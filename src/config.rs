@@ -0,0 +1,94 @@
+use neat::activation::ActivationFunction;
+
+/// Settings for turning a CPPN into a substrate network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HyperNeatConfig {
+    /// Whether a constant 1.0 is appended to every CPPN query.
+    pub cppn_bias: bool,
+    /// Activation function the CPPN output nodes are compiled with,
+    /// overriding the evolved one. It has to be signed for the CPPN to
+    /// produce negative weights; `None` keeps the genome's activation.
+    pub cppn_output_activation: Option<ActivationFunction>,
+    /// Whether the second CPPN output is a link expression output: a
+    /// connection is expressed iff it is positive, and the weight threshold
+    /// is not applied.
+    pub leo:       bool,
+
+    /// CPPN outputs with a magnitude below this value do not create a
+    /// connection.
+    pub weight_threshold: f64,
+    /// Magnitude of the weight created by a CPPN output of ±1.
+    pub max_weight:       f64,
+
+    /// Activation function of the hidden and output nodes of the substrate.
+    pub activation: ActivationFunction,
+
+    /// Learning rate of an adaptive connection whose CPPN learning rate
+    /// output is 1.
+    pub learning_rate: f64,
+
+    // ES-HyperNEAT
+    /// Depth to which the quadtree is always divided.
+    pub initial_depth:      usize,
+    /// Depth beyond which the quadtree is never divided.
+    pub max_depth:          usize,
+    /// Variance of the raw CPPN weight output above which a quadtree cell
+    /// between `initial_depth` and `max_depth` is divided further.
+    pub division_threshold: f64,
+    /// Variance of the raw CPPN weight output below which a cell is
+    /// considered uniform enough to place a node in it.
+    pub variance_threshold: f64,
+    /// Minimum difference in raw CPPN weight output to the neighbouring cells
+    /// for a node to be placed, so that nodes only appear on bands of the
+    /// pattern.
+    pub band_threshold:     f64,
+    /// Number of times newly discovered hidden nodes are explored for
+    /// further hidden nodes.
+    pub iteration_level:    usize,
+}
+
+impl Default for HyperNeatConfig {
+    fn default() -> Self {
+        Self {
+            cppn_bias: true,
+            cppn_output_activation: Some(ActivationFunction::Tanh),
+            leo:       false,
+
+            weight_threshold: 0.2,
+            max_weight:       5.0,
+
+            activation: ActivationFunction::Sigmoid,
+
+            learning_rate: 0.1,
+
+            initial_depth:      1,
+            max_depth:          3,
+            division_threshold: 0.5,
+            variance_threshold: 0.03,
+            band_threshold:     0.3,
+            iteration_level:    1,
+        }
+    }
+}
+
+impl HyperNeatConfig {
+    /// Number of CPPN outputs read for every connection: the weight, and
+    /// the link expression output if `leo` is set.
+    #[inline]
+    pub fn num_cppn_outputs(&self) -> usize {
+        1 + self.leo as usize
+    }
+
+    /// Maps a raw CPPN output to a connection weight. Outputs within the
+    /// threshold yield `None`, the remaining range is scaled linearly so that
+    /// an output of ±1 results in ±`max_weight`.
+    pub fn scale_weight(&self, output: f64) -> Option<f64> {
+        let w = output.clamp(-1.0, 1.0);
+        if w.abs() <= self.weight_threshold {
+            return None;
+        }
+        let scaled = (w.abs() - self.weight_threshold) / 
+            (1.0 - self.weight_threshold);
+        Some(scaled.copysign(w) * self.max_weight)
+    }
+}
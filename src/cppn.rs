@@ -0,0 +1,184 @@
+use std::{error::Error, fmt};
+
+use neat::{genome::Genome, nn::FeedForwardNetwork};
+
+use crate::config::HyperNeatConfig;
+
+/// Error raised when a genome does not fit the queries made by a
+/// [`HyperNeatConfig`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum CppnError {
+    /// The genome has fewer outputs than the config reads.
+    MissingOutputs { required: usize, found: usize },
+}
+
+impl fmt::Display for CppnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingOutputs { required, found } => write!(f, 
+                "the CPPN needs {} outputs, but has {}", required, found),
+        }
+    }
+}
+
+impl Error for CppnError {}
+
+/// Compositional pattern producing network: a feed-forward network evolved by
+/// NEAT that is queried with the coordinates of two substrate nodes.
+#[derive(Debug, Clone)]
+pub struct Cppn {
+    network: FeedForwardNetwork,
+    bias:    bool,
+}
+
+impl Cppn {
+    /// Compiles `genome` into a CPPN whose output nodes use 
+    /// `config.cppn_output_activation`. If `config.cppn_bias` is set, a 
+    /// constant 1.0 is passed as the last input of every query.
+    ///
+    /// Fails if `genome` lacks the link expression output that `config.leo`
+    /// asks for.
+    pub fn create(genome: &Genome, config: &HyperNeatConfig) 
+            -> Result<Self, CppnError> {
+        Self::check_outputs(genome, config.num_cppn_outputs())?;
+        let network = match config.cppn_output_activation {
+            Some(activation) => {
+                let mut genome = genome.clone();
+                for key in genome.output_keys().to_vec() {
+                    genome.node_mut(key).unwrap().set_activation(activation);
+                }
+                FeedForwardNetwork::create(&genome)
+            }
+            None => FeedForwardNetwork::create(genome),
+        };
+        Ok(Self {
+            network,
+            bias: config.cppn_bias,
+        })
+    }
+
+    /// Fails unless `genome` has at least `required` outputs.
+    pub(crate) fn check_outputs(genome: &Genome, required: usize) 
+            -> Result<(), CppnError> {
+        let found = genome.output_keys().len();
+        if found < required {
+            return Err(CppnError::MissingOutputs { required, found });
+        }
+        Ok(())
+    }
+
+    /// Evaluates the CPPN for the connection from `src` to `dst` and returns
+    /// all of its outputs.
+    pub fn query(&mut self, src: &[f64], dst: &[f64]) -> Vec<f64> {
+        let mut inputs = Vec::with_capacity(src.len() + dst.len() + 1);
+        inputs.extend_from_slice(src);
+        inputs.extend_from_slice(dst);
+        if self.bias {
+            inputs.push(1.0);
+        }
+        self.network.activate(&inputs)
+    }
+
+    /// Weight of the substrate connection from `src` to `dst`, or `None` if
+    /// the connection is not expressed.
+    pub fn query_weight(&mut self, src: &[f64], dst: &[f64], 
+            config: &HyperNeatConfig) -> Option<f64> {
+        let outputs = self.query(src, dst);
+        if config.leo {
+            (outputs[1] > 0.0)
+                .then(|| outputs[0].clamp(-1.0, 1.0) * config.max_weight)
+        } else {
+            config.scale_weight(outputs[0])
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use neat::{
+        config::{InitialConnection, NeatConfig},
+        innovation::InnovationTracker,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    /// CPPN for 2D substrates whose weight output is `x2` plus `bias`,
+    /// passed through the output activation.
+    fn x2_cppn(bias: f64, config: &HyperNeatConfig) -> Cppn {
+        let neat_config = NeatConfig {
+            num_inputs: 5,
+            num_outputs: 1,
+            initial_connection: InitialConnection::Unconnected,
+            bias_init_mean: bias,
+            bias_init_stdev: 0.0,
+            ..NeatConfig::default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&neat_config);
+        let mut genome = Genome::new(0, &neat_config, &mut rng);
+        let (x2, out) = (genome.input_keys()[2], genome.output_keys()[0]);
+        genome.add_connection(&mut innovations, x2, out, 1.0, true);
+        Cppn::create(&genome, config).unwrap()
+    }
+
+    #[test]
+    fn weights_take_the_sign_of_the_output() {
+        let config = HyperNeatConfig::default();
+        let mut cppn = x2_cppn(0.0, &config);
+
+        let w = cppn.query_weight(&[0.0, 0.0], &[1.0, 0.0], &config).unwrap();
+        assert!(w > 0.0 && w <= config.max_weight);
+        let w = cppn.query_weight(&[0.0, 0.0], &[-1.0, 0.0], &config)
+            .unwrap();
+        assert!(w < 0.0 && w >= -config.max_weight);
+
+        let mut cppn = x2_cppn(-0.5, &config);
+        let w = cppn.query_weight(&[0.0, 0.0], &[0.0, 0.0], &config).unwrap();
+        assert!(w < 0.0);
+    }
+
+    #[test]
+    fn outputs_within_the_threshold_are_not_expressed() {
+        let config = HyperNeatConfig::default();
+        let mut cppn = x2_cppn(0.0, &config);
+        // tanh(2.5 * 0.05) is about 0.12
+        assert_eq!(cppn.query_weight(&[0.0, 0.0], &[0.05, 0.0], &config), 
+            None);
+        assert_eq!(cppn.query_weight(&[0.0, 0.0], &[-0.05, 0.0], &config), 
+            None);
+        assert!(cppn.query_weight(&[0.0, 0.0], &[0.2, 0.0], &config)
+            .is_some());
+    }
+
+    #[test]
+    fn genome_activation_is_kept_without_override() {
+        let config = HyperNeatConfig {
+            cppn_output_activation: None,
+            ..HyperNeatConfig::default()
+        };
+        // the default sigmoid output never goes below zero
+        let mut cppn = x2_cppn(0.0, &config);
+        let outputs = cppn.query(&[0.0, 0.0], &[-1.0, 0.0]);
+        assert!(outputs[0] > 0.0 && outputs[0] < 0.5);
+    }
+
+    #[test]
+    fn leo_requires_a_second_output() {
+        let neat_config = NeatConfig {
+            num_inputs: 5,
+            num_outputs: 1,
+            ..NeatConfig::default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let genome = Genome::new(0, &neat_config, &mut rng);
+        let config = HyperNeatConfig {
+            leo: true,
+            ..HyperNeatConfig::default()
+        };
+        assert_eq!(Cppn::create(&genome, &config).unwrap_err(), 
+            CppnError::MissingOutputs { required: 2, found: 1 });
+        assert!(Cppn::create(&genome, &HyperNeatConfig::default()).is_ok());
+    }
+}
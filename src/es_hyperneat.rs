@@ -0,0 +1,401 @@
+//! Evolvable-substrate HyperNEAT: hidden nodes are not laid out by hand, but
+//! placed where the weight pattern of the CPPN carries the most information.
+//!
+//! Starting from every input, the space of outgoing connections is divided
+//! into a quadtree, more finely where the CPPN output varies. Nodes are
+//! placed in the leaves that lie on a band of the pattern, i.e. differ
+//! sufficiently from their neighbours. Hidden nodes found this way are
+//! explored in turn, and finally the incoming connections of every output are
+//! searched the same way. Only hidden nodes on a path from an input to an
+//! output are kept.
+
+use std::collections::{HashMap, HashSet};
+
+use neat::genome::Genome;
+
+use crate::{
+    config::HyperNeatConfig,
+    cppn::{Cppn, CppnError},
+    network::{Connection, SubstrateNetwork},
+    substrate::Substrate,
+};
+
+type Point = (f64, f64);
+
+#[inline]
+fn point_key(p: Point) -> (u64, u64) {
+    (p.0.to_bits(), p.1.to_bits())
+}
+
+/// Cell of the quadtree, centred on `x`, `y` and extending `width` in every
+/// direction.
+struct QuadPoint {
+    x:      f64,
+    y:      f64,
+    width:  f64,
+    level:  usize,
+    weight: f64,
+
+    children: Vec<QuadPoint>,
+}
+
+impl QuadPoint {
+    fn new(x: f64, y: f64, width: f64, level: usize) -> Self {
+        Self { x, y, width, level, weight: 0.0, children: Vec::new() }
+    }
+
+    fn leaf_weights(&self, weights: &mut Vec<f64>) {
+        if self.children.is_empty() {
+            weights.push(self.weight);
+        }
+        for c in self.children.iter() {
+            c.leaf_weights(weights);
+        }
+    }
+
+    /// Variance of the weights of all leaves below this cell.
+    fn variance(&self) -> f64 {
+        if self.children.is_empty() {
+            return 0.0;
+        }
+        let mut weights = Vec::new();
+        self.leaf_weights(&mut weights);
+        let n = weights.len() as f64;
+        let mean = weights.iter().sum::<f64>() / n;
+        weights.iter().map(|w| (w - mean).powi(2)).sum::<f64>() / n
+    }
+}
+
+struct Explorer<'a> {
+    cppn:   Cppn,
+    config: &'a HyperNeatConfig,
+}
+
+impl Explorer<'_> {
+    /// Raw CPPN weight output for the connection between `coord` and `p`,
+    /// leaving `coord` if `outgoing` is set and entering it otherwise. The
+    /// quadtree thresholds apply to this value.
+    fn query(&mut self, coord: Point, p: Point, outgoing: bool) -> f64 {
+        let (a, b) = ([coord.0, coord.1], [p.0, p.1]);
+        let (src, dst) = if outgoing { (a, b) } else { (b, a) };
+        self.cppn.query(&src, &dst)[0]
+    }
+
+    /// Weight of the substrate connection between `coord` and `p`, or
+    /// `None` if it is not expressed.
+    fn connection_weight(&mut self, coord: Point, p: Point, outgoing: bool) 
+            -> Option<f64> {
+        let (a, b) = ([coord.0, coord.1], [p.0, p.1]);
+        let (src, dst) = if outgoing { (a, b) } else { (b, a) };
+        self.cppn.query_weight(&src, &dst, self.config)
+    }
+
+    fn divide(&mut self, p: &mut QuadPoint, coord: Point, outgoing: bool) {
+        let half = p.width / 2.0;
+        for (dx, dy) in [(-1.0, -1.0), (-1.0, 1.0), (1.0, 1.0), (1.0, -1.0)] {
+            let mut c = QuadPoint::new(p.x + dx * half, p.y + dy * half, half,
+                p.level + 1);
+            c.weight = self.query(coord, (c.x, c.y), outgoing);
+            p.children.push(c);
+        }
+
+        if p.level < self.config.initial_depth || 
+                (p.level < self.config.max_depth && 
+                 p.variance() > self.config.division_threshold) {
+            for c in p.children.iter_mut() {
+                self.divide(c, coord, outgoing);
+            }
+        }
+    }
+
+    fn prune(&mut self, p: &QuadPoint, coord: Point, outgoing: bool,
+            found: &mut Vec<(Point, f64)>) {
+        for c in p.children.iter() {
+            if c.variance() > self.config.variance_threshold {
+                self.prune(c, coord, outgoing, found);
+                continue;
+            }
+            let mut band = |x: f64, y: f64| {
+                (c.weight - self.query(coord, (x, y), outgoing)).abs()
+            };
+            let left = band(c.x - p.width, c.y);
+            let right = band(c.x + p.width, c.y);
+            let top = band(c.x, c.y - p.width);
+            let bottom = band(c.x, c.y + p.width);
+
+            if top.min(bottom).max(left.min(right)) <= 
+                    self.config.band_threshold {
+                continue;
+            }
+            if let Some(weight) = self.connection_weight(coord, (c.x, c.y), 
+                    outgoing) {
+                found.push(((c.x, c.y), weight));
+            }
+        }
+    }
+
+    /// Points connected to `coord` together with the connection weights.
+    fn explore(&mut self, coord: Point, outgoing: bool) -> Vec<(Point, f64)> {
+        let mut root = QuadPoint::new(0.0, 0.0, 1.0, 1);
+        self.divide(&mut root, coord, outgoing);
+        let mut found = Vec::new();
+        self.prune(&root, coord, outgoing, &mut found);
+        found
+    }
+}
+
+/// Discovers the hidden nodes and connections of a network for a two
+/// dimensional `substrate` by querying the CPPN compiled from `genome`.
+/// Hidden coordinates of the substrate are ignored.
+pub fn create_network(substrate: &Substrate, genome: &Genome,
+        config: &HyperNeatConfig) -> Result<SubstrateNetwork, CppnError> {
+    assert_eq!(substrate.dimensions(), 2, 
+        "ES-HyperNEAT requires a two dimensional substrate");
+
+    let mut explorer = Explorer {
+        cppn: Cppn::create(genome, config)?,
+        config,
+    };
+    let to_point = |c: &Vec<f64>| (c[0], c[1]);
+    let inputs: Vec<Point> = substrate.input_coordinates().iter()
+        .map(to_point)
+        .collect();
+    let outputs: Vec<Point> = substrate.output_coordinates().iter()
+        .map(to_point)
+        .collect();
+
+    let mut links: Vec<(Point, Point, f64)> = Vec::new();
+    let mut hidden: Vec<Point> = Vec::new();
+    let mut known: HashSet<(u64, u64)> = inputs.iter().chain(outputs.iter())
+        .map(|&p| point_key(p))
+        .collect();
+
+    let mut unexplored = inputs.clone();
+    for _ in 0..=config.iteration_level {
+        let mut discovered = Vec::new();
+        for &src in unexplored.iter() {
+            for (dst, weight) in explorer.explore(src, true) {
+                if known.insert(point_key(dst)) {
+                    hidden.push(dst);
+                    discovered.push(dst);
+                }
+                links.push((src, dst, weight));
+            }
+        }
+        unexplored = discovered;
+    }
+
+    for &dst in outputs.iter() {
+        for (src, weight) in explorer.explore(dst, false) {
+            links.push((src, dst, weight));
+        }
+    }
+
+    // hidden nodes sorted bottom to top, so that a single pass along the
+    // usual direction of flow reaches most of them
+    hidden.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.total_cmp(&b.0)));
+
+    let index: HashMap<(u64, u64), usize> = inputs.iter()
+        .chain(hidden.iter())
+        .chain(outputs.iter())
+        .enumerate()
+        .map(|(k, &p)| (point_key(p), k))
+        .collect();
+    let num_inputs = inputs.len();
+    let num_hidden = hidden.len();
+    let num_nodes = num_inputs + num_hidden + outputs.len();
+    let edges: Vec<(usize, usize, f64)> = links.into_iter()
+        .filter_map(|(src, dst, w)| {
+            let src = *index.get(&point_key(src))?;
+            let dst = *index.get(&point_key(dst))?;
+            // inputs are never written to
+            (dst >= num_inputs).then_some((src, dst, w))
+        })
+        .collect();
+
+    // keep only hidden nodes that lie on a path from an input to an output
+    let reachable = |starts: Vec<usize>, forward: bool| {
+        let mut visited: HashSet<usize> = starts.iter().copied().collect();
+        let mut stack = starts;
+        while let Some(k) = stack.pop() {
+            for &(src, dst, _) in edges.iter() {
+                let (from, to) = if forward { (src, dst) } else { (dst, src) };
+                if from == k && visited.insert(to) {
+                    stack.push(to);
+                }
+            }
+        }
+        visited
+    };
+    let from_inputs = reachable((0..num_inputs).collect(), true);
+    let to_outputs = reachable(
+        (num_inputs + num_hidden..num_nodes).collect(), false);
+    let is_kept = |k: usize| {
+        k < num_inputs || k >= num_inputs + num_hidden ||
+            (from_inputs.contains(&k) && to_outputs.contains(&k))
+    };
+
+    // renumber so that the kept hidden nodes are contiguous
+    let mut renumber = vec![None; num_nodes];
+    let mut next = 0;
+    for (k, r) in renumber.iter_mut().enumerate() {
+        if is_kept(k) {
+            *r = Some(next);
+            next += 1;
+        }
+    }
+    let connections = edges.iter()
+        .filter_map(|&(src, dst, weight)| Some(Connection {
+            src: renumber[src]?,
+            dst: renumber[dst]?,
+            weight,
+        }))
+        .collect();
+
+    let coordinates: Vec<Vec<f64>> = inputs.iter()
+        .chain(hidden.iter())
+        .chain(outputs.iter())
+        .enumerate()
+        .filter(|&(k, _)| is_kept(k))
+        .map(|(_, p)| vec![p.0, p.1])
+        .collect();
+    let first_output = coordinates.len() - outputs.len();
+    Ok(SubstrateNetwork::new(
+        (0..num_inputs).collect(),
+        (num_inputs..first_output).collect(),
+        (first_output..coordinates.len()).collect(),
+        coordinates,
+        connections,
+        config.activation,
+        config.iteration_level + 1,
+    ))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use neat::{
+        activation::ActivationFunction,
+        config::{InitialConnection, NeatConfig},
+        innovation::InnovationTracker,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    /// CPPN genome for 2D substrates taking `x1, y1, x2, y2, bias`, whose
+    /// output sums `weight * inputs[i]` for every `(i, weight)` of `linear` 
+    /// and a narrow Gaussian ridge centred on `inputs[i] == c` for every 
+    /// `(i, c)` of `ridges`.
+    fn cppn_genome(linear: &[(usize, f64)], ridges: &[(usize, f64)]) 
+            -> Genome {
+        let config = NeatConfig {
+            num_inputs: 5,
+            num_outputs: 1,
+            initial_connection: InitialConnection::Unconnected,
+            bias_init_mean: 0.0,
+            bias_init_stdev: 0.0,
+            ..NeatConfig::default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&config);
+        let mut genome = Genome::new(0, &config, &mut rng);
+        let out = genome.output_keys()[0];
+        for &(i, weight) in linear {
+            let src = genome.input_keys()[i];
+            genome.add_connection(&mut innovations, src, out, weight, true);
+        }
+        for (k, &(i, c)) in ridges.iter().enumerate() {
+            let key = 6 + k;
+            let node = genome.add_node(key, &config, &mut rng);
+            node.set_activation(ActivationFunction::Gauss);
+            node.set_bias(-4.0 * c);
+            node.set_response(1.0);
+            let src = genome.input_keys()[i];
+            genome.add_connection(&mut innovations, src, key, 4.0, true);
+            genome.add_connection(&mut innovations, key, out, 1.0, true);
+        }
+        genome
+    }
+
+    fn depth(p: &QuadPoint) -> usize {
+        p.children.iter().map(|c| depth(c) + 1).max().unwrap_or(0)
+    }
+
+    fn tree(genome: &Genome, config: &HyperNeatConfig, coord: Point) 
+            -> QuadPoint {
+        let mut explorer = Explorer { 
+            cppn: Cppn::create(genome, config).unwrap(), 
+            config,
+        };
+        let mut root = QuadPoint::new(0.0, 0.0, 1.0, 1);
+        explorer.divide(&mut root, coord, true);
+        root
+    }
+
+    #[test]
+    fn only_varying_patterns_are_subdivided() {
+        let config = HyperNeatConfig::default();
+        let constant = cppn_genome(&[], &[]);
+        assert_eq!(depth(&tree(&constant, &config, (0.0, -1.0))), 1);
+
+        // tanh(2.5 * x2) differs by far more than the division threshold
+        // between the quadrants of the root
+        let varying = cppn_genome(&[(2, 1.0)], &[]);
+        assert_eq!(depth(&tree(&varying, &config, (0.0, -1.0))), 2);
+    }
+
+    #[test]
+    fn hidden_nodes_are_placed_on_bands() {
+        let config = HyperNeatConfig {
+            division_threshold: 0.03,
+            ..HyperNeatConfig::default()
+        };
+        // connections into y == 0.5 and out of y == 0.5 are strong
+        let genome = cppn_genome(&[], &[(3, 0.5), (1, 0.5)]);
+        let substrate = Substrate::new(
+            vec![vec![-0.5, -1.0], vec![0.5, -1.0]],
+            vec![],
+            vec![vec![0.0, 1.0]],
+        );
+        let network = create_network(&substrate, &genome, &config)
+            .unwrap();
+
+        let hidden = &network.coordinates()[2..network.num_nodes() - 1];
+        assert!(!hidden.is_empty());
+        assert!(hidden.iter().all(|c| c[1] == 0.5));
+        let output = network.num_nodes() - 1;
+        for k in 2..output {
+            assert!(network.connections().iter()
+                .any(|c| c.src < 2 && c.dst == k));
+            assert!(network.connections().iter()
+                .any(|c| c.src == k && c.dst == output));
+        }
+    }
+
+    #[test]
+    fn hidden_nodes_off_a_path_are_pruned() {
+        let config = HyperNeatConfig {
+            division_threshold: 0.03,
+            ..HyperNeatConfig::default()
+        };
+        // hidden nodes are found from the inputs, but nothing connects
+        // them to the output
+        let genome = cppn_genome(&[], &[(3, 0.5)]);
+        let mut explorer = Explorer { 
+            cppn: Cppn::create(&genome, &config).unwrap(),
+            config: &config,
+        };
+        assert!(!explorer.explore((-0.5, -1.0), true).is_empty());
+
+        let substrate = Substrate::new(
+            vec![vec![-0.5, -1.0], vec![0.5, -1.0]],
+            vec![],
+            vec![vec![0.0, 1.0]],
+        );
+        let network = create_network(&substrate, &genome, &config)
+            .unwrap();
+        assert_eq!(network.num_nodes(), 3);
+        assert!(network.connections().is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+//! Link expression output (LEO) seeding.
+//!
+//! With [`HyperNeatConfig::leo`](crate::config::HyperNeatConfig::leo) set,
+//! the second output of the CPPN decides which connections exist. Starting
+//! evolution from CPPNs whose LEO only expresses connections between nodes
+//! that are close to each other, as proposed by Verbancsics and Stanley,
+//! biases the search towards modular, local connectivity.
+
+use hashbrown::HashMap;
+use neat::{
+    activation::ActivationFunction,
+    config::NeatConfig,
+    genome::Genome,
+    innovation::InnovationTracker,
+    population::Population,
+};
+use rand::Rng;
+
+/// Rewires the LEO of `genome`, its second output, to one Gaussian node per
+/// entry of `axes`, each computing the distance of source and target along 
+/// that axis. The LEO is then positive only if the nodes are close along all
+/// of these axes. The original paper seeds locality along x only, i.e. 
+/// `axes == [0]`, leaving the axis along which layers are stacked free.
+///
+/// The genome has to be laid out for a substrate with `dimensions` axes, i.e.
+/// take the source coordinates followed by the target coordinates. The
+/// Gaussian nodes get the keys directly after the configured hidden nodes, 
+/// so they line up across all genomes seeded with the same `config`. Their
+/// connections are numbered by `innovations`, which also reserves their
+/// keys so that later mutations do not hand them out again.
+pub fn seed_locality<R: Rng>(genome: &mut Genome, config: &NeatConfig, 
+        innovations: &mut InnovationTracker, dimensions: usize, 
+        axes: &[usize], rng: &mut R) {
+    assert!(config.num_outputs >= 2, "the CPPN has no link expression output");
+    assert!(config.num_inputs >= 2 * dimensions, 
+        "the CPPN does not take {} dimensional coordinates", dimensions);
+    assert!(axes.iter().all(|&a| a < dimensions), "axis out of range");
+
+    let leo = genome.output_keys()[1];
+    let incoming: Vec<usize> = genome.connections().values()
+        .filter(|c| c.get_dst() == leo)
+        .map(|c| c.get_id())
+        .collect();
+    for id in incoming {
+        genome.remove_connection(id);
+    }
+
+    let first_key = config.num_inputs + config.num_outputs + config.num_hidden;
+    for (i, &axis) in axes.iter().enumerate() {
+        let key = first_key + i;
+        let node = genome.add_node(key, config, rng);
+        node.set_activation(ActivationFunction::Gauss);
+        node.set_bias(0.0);
+        node.set_response(1.0);
+
+        let src = genome.input_keys()[axis];
+        let dst = genome.input_keys()[dimensions + axis];
+        genome.add_connection(innovations, src, key, 1.0, true);
+        genome.add_connection(innovations, dst, key, -1.0, true);
+        genome.add_connection(innovations, key, leo, 1.0, true);
+    }
+
+    // the LEO computes `sum - (n - 0.5)` over the n gaussians, each of which
+    // is at most 1, so it is only positive if all of them are above one half
+    let node = genome.node_mut(leo).unwrap();
+    node.set_activation(ActivationFunction::Identity);
+    node.set_bias(0.5 - axes.len() as f64);
+    node.set_response(1.0);
+
+    innovations.register(genome);
+}
+
+/// Creates a population whose genomes are all seeded by [`seed_locality`],
+/// drawing their initial attributes from `rng`.
+pub fn seeded_population<R: Rng>(config: NeatConfig, dimensions: usize, 
+        axes: &[usize], rng: &mut R) -> Population {
+    let mut innovations = InnovationTracker::new(&config);
+    let genomes: HashMap<usize, Genome> = (0..config.pop_size)
+        .map(|id| {
+            let mut genome = Genome::new(id, &config, rng);
+            seed_locality(&mut genome, &config, &mut innovations, dimensions,
+                axes, rng);
+            (id, genome)
+        })
+        .collect();
+    Population::from_genomes(config, genomes)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::{config::HyperNeatConfig, cppn::Cppn};
+
+    #[test]
+    fn seeded_leo_only_expresses_local_connections() {
+        let config = NeatConfig {
+            num_inputs: 5,
+            num_outputs: 2,
+            ..NeatConfig::default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&config);
+        let mut genome = Genome::new(0, &config, &mut rng);
+        seed_locality(&mut genome, &config, &mut innovations, 2, &[0], 
+            &mut rng);
+
+        let mut cppn = Cppn::create(&genome, &HyperNeatConfig::default())
+            .unwrap();
+        assert!(cppn.query(&[0.0, -1.0], &[0.1, 1.0])[1] > 0.0);
+        assert!(cppn.query(&[0.5, 1.0], &[0.3, 0.0])[1] > 0.0);
+        assert!(cppn.query(&[0.0, -1.0], &[0.8, -1.0])[1] < 0.0);
+        assert!(cppn.query(&[-0.5, 0.0], &[0.5, 0.0])[1] < 0.0);
+    }
+
+    #[test]
+    fn seeded_nodes_are_not_handed_out_again() {
+        let config = NeatConfig {
+            num_inputs: 5,
+            num_outputs: 2,
+            node_add_prob: 1.0,
+            node_delete_prob: 0.0,
+            conn_add_prob: 0.0,
+            conn_delete_prob: 0.0,
+            ..NeatConfig::default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&config);
+        let mut genome = Genome::new(0, &config, &mut rng);
+        seed_locality(&mut genome, &config, &mut innovations, 2, &[0, 1], 
+            &mut rng);
+
+        let num_nodes = genome.nodes().len();
+        genome.mutate(&config, &mut innovations, &mut rng);
+        assert_eq!(genome.nodes().len(), num_nodes + 1);
+    }
+}
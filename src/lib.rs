@@ -1 +1,9 @@
-use neat::genome;
\ No newline at end of file
+pub mod adaptive;
+pub mod config;
+pub mod cppn;
+pub mod es_hyperneat;
+pub mod leo;
+pub mod network;
+pub mod substrate;
+
+pub use neat;
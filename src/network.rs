@@ -0,0 +1,294 @@
+use std::fmt::Write as _;
+
+use neat::{activation::ActivationFunction, genome::Genome};
+
+use crate::{
+    config::HyperNeatConfig,
+    cppn::{Cppn, CppnError},
+    substrate::Substrate,
+};
+
+/// Weighted link between two substrate nodes, identified by their index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Connection {
+    pub src:    usize,
+    pub dst:    usize,
+    pub weight: f64,
+}
+
+/// Network whose connection weights were generated by querying a CPPN.
+///
+/// Nodes are indexed inputs first, then hidden nodes, then outputs. Every
+/// activation evaluates the hidden and output nodes in that order, which is
+/// repeated `passes` times so that signals can travel along connections that
+/// point backwards in this order.
+#[derive(Debug, Clone)]
+pub struct SubstrateNetwork {
+    inputs:  Vec<usize>,
+    outputs: Vec<usize>,
+    order:   Vec<usize>,
+    passes:  usize,
+
+    coordinates: Vec<Vec<f64>>,
+    connections: Vec<Connection>,
+    incoming:    Vec<Vec<usize>>,
+    activation:  ActivationFunction,
+    values:      Vec<f64>,
+}
+
+impl SubstrateNetwork {
+    /// Queries the CPPN compiled from `genome` for every pair of nodes in
+    /// consecutive substrate layers. Only connections for which
+    /// [`Cppn::query_weight`] yields a weight are expressed, i.e. those
+    /// above `config.weight_threshold` or, with `config.leo`, those with a
+    /// positive link expression output.
+    pub fn create(substrate: &Substrate, genome: &Genome, 
+            config: &HyperNeatConfig) -> Result<Self, CppnError> {
+        let mut cppn = Cppn::create(genome, config)?;
+        let coordinates = substrate.layers();
+
+        let mut layers: Vec<Vec<usize>> = Vec::new();
+        let mut num_nodes = 0;
+        for layer in coordinates.iter() {
+            layers.push((num_nodes..num_nodes + layer.len()).collect());
+            num_nodes += layer.len();
+        }
+
+        let mut connections = Vec::new();
+        for (l, pair) in coordinates.windows(2).enumerate() {
+            for (i, src) in pair[0].iter().enumerate() {
+                for (j, dst) in pair[1].iter().enumerate() {
+                    if let Some(weight) = cppn.query_weight(src, dst, config) {
+                        connections.push(Connection {
+                            src: layers[l][i],
+                            dst: layers[l + 1][j],
+                            weight,
+                        });
+                    }
+                }
+            }
+        }
+
+        let inputs = layers.remove(0);
+        let outputs = layers.pop().unwrap();
+        let hidden = layers.into_iter().flatten().collect();
+        let coordinates = coordinates.into_iter().flatten().cloned().collect();
+        Ok(Self::new(inputs, hidden, outputs, coordinates, connections, 
+            config.activation, 1))
+    }
+
+    pub(crate) fn new(inputs: Vec<usize>, hidden: Vec<usize>, 
+            outputs: Vec<usize>, coordinates: Vec<Vec<f64>>,
+            connections: Vec<Connection>, activation: ActivationFunction,
+            passes: usize) -> Self {
+        let num_nodes = coordinates.len();
+        let mut incoming = vec![Vec::new(); num_nodes];
+        for (i, conn) in connections.iter().enumerate() {
+            incoming[conn.dst].push(i);
+        }
+
+        let mut order = hidden;
+        order.extend_from_slice(&outputs);
+        Self {
+            inputs,
+            outputs,
+            order,
+            passes,
+            coordinates,
+            connections,
+            incoming,
+            activation,
+            values: vec![0.0; num_nodes],
+        }
+    }
+
+    #[inline]
+    pub fn connections(&self) -> &[Connection] {
+        &self.connections
+    }
+
+    #[inline]
+    pub(crate) fn connections_mut(&mut self) -> &mut [Connection] {
+        &mut self.connections
+    }
+
+    /// Substrate coordinates of every node, by index.
+    #[inline]
+    pub fn coordinates(&self) -> &[Vec<f64>] {
+        &self.coordinates
+    }
+
+    #[inline]
+    pub fn num_nodes(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Values of all nodes after the last activation, by index.
+    #[inline]
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Propagates `inputs` through the network and returns the values of the
+    /// output nodes.
+    pub fn activate(&mut self, inputs: &[f64]) -> Vec<f64> {
+        assert_eq!(inputs.len(), self.inputs.len(), 
+            "expected {} inputs", self.inputs.len());
+
+        self.values.iter_mut().for_each(|v| *v = 0.0);
+        for (&k, &v) in self.inputs.iter().zip(inputs) {
+            self.values[k] = v;
+        }
+
+        for _ in 0..self.passes {
+            for &k in self.order.iter() {
+                let sum = self.incoming[k].iter()
+                    .map(|&i| &self.connections[i])
+                    .map(|c| self.values[c.src] * c.weight)
+                    .sum();
+                self.values[k] = self.activation.activate(sum);
+            }
+        }
+
+        self.outputs.iter().map(|&k| self.values[k]).collect()
+    }
+
+    /// Renders the network as a Graphviz DOT graph with every node pinned to
+    /// its substrate position (the first two coordinates), for `neato -n`.
+    /// Edge colours and widths follow [`neat::dot::genome_to_dot`].
+    pub fn to_dot(&self) -> String {
+        let mut ret = String::from("digraph substrate {\n");
+        writeln!(ret, "    node [fontsize=9, height=0.2, width=0.2];").unwrap();
+        for (k, c) in self.coordinates.iter().enumerate() {
+            let fill = if self.inputs.contains(&k) {
+                "lightgray"
+            } else if self.outputs.contains(&k) {
+                "lightblue"
+            } else {
+                "white"
+            };
+            writeln!(ret, "    n{} [pos=\"{:.1},{:.1}!\", style=filled, \
+                fillcolor={}];", k, c[0] * 100.0, c[1] * 100.0, fill).unwrap();
+        }
+        for conn in self.connections.iter() {
+            writeln!(ret, "    n{} -> n{} [color={}, penwidth={:.3}];", 
+                conn.src, conn.dst,
+                if conn.weight > 0.0 { "green" } else { "red" },
+                0.1 + conn.weight.abs() / 5.0).unwrap();
+        }
+        ret.push_str("}\n");
+        ret
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use neat::{
+        config::{InitialConnection, NeatConfig},
+        innovation::InnovationTracker,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    /// CPPN genome with a single output that sums `weight * inputs[i]` for
+    /// every `(i, weight)` of `links`.
+    fn cppn_genome(num_inputs: usize, links: &[(usize, f64)]) -> Genome {
+        let config = NeatConfig {
+            num_inputs,
+            num_outputs: 1,
+            initial_connection: InitialConnection::Unconnected,
+            bias_init_mean: 0.0,
+            bias_init_stdev: 0.0,
+            ..NeatConfig::default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut innovations = InnovationTracker::new(&config);
+        let mut genome = Genome::new(0, &config, &mut rng);
+        let out = genome.output_keys()[0];
+        for &(i, weight) in links {
+            let src = genome.input_keys()[i];
+            genome.add_connection(&mut innovations, src, out, weight, true);
+        }
+        genome
+    }
+
+    #[test]
+    fn consecutive_layers_are_connected() {
+        let substrate = Substrate::new(
+            vec![vec![-1.0, -1.0], vec![1.0, -1.0]],
+            vec![vec![vec![-1.0, 0.0], vec![0.0, 0.0], vec![1.0, 0.0]]],
+            vec![vec![0.0, 1.0]],
+        );
+        // only the bias input is connected, so every connection is expressed
+        let genome = cppn_genome(5, &[(4, 1.0)]);
+        let config = HyperNeatConfig::default();
+        let network = SubstrateNetwork::create(&substrate, &genome, &config)
+            .unwrap();
+
+        assert_eq!(network.num_nodes(), 6);
+        assert_eq!(network.coordinates()[2], [-1.0, 0.0]);
+        let mut links: Vec<(usize, usize)> = network.connections().iter()
+            .map(|c| (c.src, c.dst))
+            .collect();
+        links.sort_unstable();
+        assert_eq!(links, [(0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), 
+            (2, 5), (3, 5), (4, 5)]);
+    }
+
+    #[test]
+    fn bias_input_is_appended() {
+        let substrate = Substrate::new(
+            vec![vec![0.0, -1.0]], 
+            vec![], 
+            vec![vec![0.0, 1.0]],
+        );
+        let genome = cppn_genome(5, &[(4, 1.0)]);
+        let config = HyperNeatConfig::default();
+        let network = SubstrateNetwork::create(&substrate, &genome, &config)
+            .unwrap();
+        let expected = config.scale_weight(2.5f64.tanh()).unwrap();
+        assert_eq!(network.connections(), [Connection {
+            src: 0,
+            dst: 1,
+            weight: expected,
+        }]);
+
+        let genome = cppn_genome(4, &[]);
+        let config = HyperNeatConfig {
+            cppn_bias: false,
+            ..config
+        };
+        let network = SubstrateNetwork::create(&substrate, &genome, &config)
+            .unwrap();
+        assert!(network.connections().is_empty());
+    }
+
+    #[test]
+    fn three_dimensional_coordinates_are_queried() {
+        let substrate = Substrate::new(
+            vec![vec![0.0, 0.0, 0.0]], 
+            vec![], 
+            vec![vec![0.0, 0.0, 1.0], vec![0.0, 0.0, -1.0]],
+        );
+        // the weight follows z2
+        let genome = cppn_genome(7, &[(5, 1.0)]);
+        let config = HyperNeatConfig::default();
+        let mut network = SubstrateNetwork::create(&substrate, &genome, 
+            &config).unwrap();
+
+        let connections = network.connections();
+        assert_eq!(connections.len(), 2);
+        assert!(connections.iter().all(|c| c.src == 0));
+        let weight = |dst| connections.iter()
+            .find(|c| c.dst == dst)
+            .unwrap()
+            .weight;
+        assert!(weight(1) > 0.0);
+        assert_eq!(weight(2), -weight(1));
+
+        let outputs = network.activate(&[1.0]);
+        assert!(outputs[0] > 0.5 && outputs[1] < 0.5);
+    }
+}
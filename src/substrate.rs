@@ -0,0 +1,105 @@
+/// Geometric layout of the neurons of a substrate network.
+///
+/// All coordinates have the same number of dimensions, either two or three.
+/// Hidden nodes are grouped into layers which are connected in order: 
+/// inputs to the first hidden layer, each hidden layer to the next, and the
+/// last one to the outputs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Substrate {
+    dimensions: usize,
+
+    input_coordinates:  Vec<Vec<f64>>,
+    hidden_coordinates: Vec<Vec<Vec<f64>>>,
+    output_coordinates: Vec<Vec<f64>>,
+}
+
+impl Substrate {
+    pub fn new(input_coordinates: Vec<Vec<f64>>, 
+            hidden_coordinates: Vec<Vec<Vec<f64>>>,
+            output_coordinates: Vec<Vec<f64>>) -> Self {
+        let dimensions = input_coordinates.first()
+            .or(output_coordinates.first())
+            .map_or(2, |c| c.len());
+        assert!(dimensions == 2 || dimensions == 3, 
+            "coordinates must be two or three dimensional");
+        assert!(input_coordinates.iter()
+                .chain(hidden_coordinates.iter().flatten())
+                .chain(output_coordinates.iter())
+                .all(|c| c.len() == dimensions),
+            "all coordinates must have {} dimensions", dimensions);
+
+        Self {
+            dimensions,
+            input_coordinates,
+            hidden_coordinates,
+            output_coordinates,
+        }
+    }
+
+    #[inline]
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Number of inputs a CPPN needs to be queried for this substrate.
+    #[inline]
+    pub fn num_cppn_inputs(&self, bias: bool) -> usize {
+        2 * self.dimensions + bias as usize
+    }
+
+    #[inline]
+    pub fn input_coordinates(&self) -> &[Vec<f64>] {
+        &self.input_coordinates
+    }
+
+    #[inline]
+    pub fn hidden_coordinates(&self) -> &[Vec<Vec<f64>>] {
+        &self.hidden_coordinates
+    }
+
+    #[inline]
+    pub fn output_coordinates(&self) -> &[Vec<f64>] {
+        &self.output_coordinates
+    }
+
+    /// The input layer, all hidden layers and the output layer, in order.
+    pub fn layers(&self) -> Vec<&[Vec<f64>]> {
+        let mut ret: Vec<&[Vec<f64>]> = vec![&self.input_coordinates];
+        ret.extend(self.hidden_coordinates.iter().map(|l| l.as_slice()));
+        ret.push(&self.output_coordinates);
+        ret
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_are_listed_in_order() {
+        let substrate = Substrate::new(
+            vec![vec![0.0, -1.0, 0.0]],
+            vec![vec![vec![0.0, 0.0, 0.0]], vec![vec![0.0, 0.5, 0.0]]],
+            vec![vec![0.0, 1.0, 0.0]],
+        );
+        assert_eq!(substrate.dimensions(), 3);
+        assert_eq!(substrate.num_cppn_inputs(true), 7);
+        assert_eq!(substrate.num_cppn_inputs(false), 6);
+
+        let ys: Vec<f64> = substrate.layers().iter()
+            .map(|layer| layer[0][1])
+            .collect();
+        assert_eq!(ys, [-1.0, 0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "all coordinates must have 2 dimensions")]
+    fn mixed_dimensions_are_rejected() {
+        Substrate::new(
+            vec![vec![0.0, -1.0]],
+            vec![vec![vec![0.0, 0.0, 0.0]]],
+            vec![vec![0.0, 1.0]],
+        );
+    }
+}